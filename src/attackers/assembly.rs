@@ -0,0 +1,62 @@
+// Text assembly for the script vm in `apply`
+// A script is `init <x> <y>` followed by mnemonics, `;` starts a comment
+//
+//     init 0 1       ; initial registers
+//     load           ; y = state[y]
+//     swap
+//     byte0          ; y = bytes[y]
+
+// mnemonics of script instructions, indexed by `instr % 19`
+pub const MNEMONICS: [&str; 19] = [
+    "add", "sub", "mul", "div", "rem", "nop", "shl", "shr",
+    "and", "or", "xor", "not", "swap", "store", "load",
+    "byte0", "byte1", "byte2", "byte3",
+];
+
+// parse a number in decimal or 0x-prefixed hexadecimal
+fn parse_word(token: &str) -> Option<u32> {
+    match token.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => token.parse().ok(),
+    }
+}
+
+// assemble script text into a script
+pub fn assemble(text: &str) -> Result<Vec<u32>, String> {
+    let mut script = vec![0, 0];
+    let mut seen_instr = false;
+    for (nr, line) in text.lines().enumerate() {
+        let line = line.split(';').next().unwrap();
+        let mut tokens = line.split_whitespace();
+        while let Some(token) = tokens.next() {
+            let token = token.to_ascii_lowercase();
+            if token == "init" {
+                if seen_instr { return Err(format!("line {}: init must come first", nr + 1)) }
+                for i in 0..2 {
+                    let word = tokens.next()
+                        .ok_or_else(|| format!("line {}: init expects two operands", nr + 1))?;
+                    script[i] = parse_word(word)
+                        .ok_or_else(|| format!("line {}: bad operand {word:?}", nr + 1))?;
+                }
+                seen_instr = true;
+                continue;
+            }
+            let Some(code) = MNEMONICS.iter().position(|m| *m == token)
+                else { return Err(format!("line {}: unknown mnemonic {token:?}", nr + 1)) };
+            script.push(code as u32);
+            seen_instr = true;
+        }
+    }
+    Ok(script)
+}
+
+// print a script as text, one instruction per line
+pub fn disassemble(script: &[u32]) -> String {
+    let get = |i: usize| script.get(i).copied().unwrap_or(0);
+    let mut text = format!("init {:#x} {:#x}\n", get(0), get(1));
+    for instr in script.iter().skip(2) {
+        text.push_str(MNEMONICS[(instr % 19) as usize]);
+        text.push('\n');
+    }
+    text
+}
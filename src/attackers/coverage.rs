@@ -0,0 +1,150 @@
+use std::sync::{Arc, Mutex};
+use rand::prelude::*;
+use revm::primitives::*;
+use crate::environment::Coverage;
+use super::{AttackerFixed, CallGroup, FuzzCorpus};
+
+// coverage guided mutation of call groups, in the style of afl
+// the corpus must be seeded with at least one call, e.g. from fuzz_campaign
+pub struct CoverageFuzzer {
+    pub corpus: FuzzCorpus,
+    // program counters reached by any kept input
+    pub coverage: Coverage,
+    // addresses used when a calldata word is replaced
+    addresses: Vec<B160>,
+    rng: StdRng,
+}
+
+impl CoverageFuzzer {
+    pub fn new(corpus: FuzzCorpus, addresses: Vec<B160>, seed: u64) -> Self {
+        Self { corpus, coverage: Coverage::default(), addresses, rng: StdRng::seed_from_u64(seed) }
+    }
+
+    // mutate one corpus entry and play it
+    // game plays an attacker with a coverage sink attached and returns the payoff, None if it fails
+    // returns whether the mutated input was kept
+    pub fn step(&mut self, game: impl Fn(AttackerFixed, Arc<Mutex<Coverage>>) -> Option<f64>) -> bool {
+        if self.corpus.entries.is_empty() { return false }
+        let i = self.rng.gen_range(0..self.corpus.entries.len());
+        let mut plan = self.corpus.entries[i].0.clone();
+        for _ in 0..1 << self.rng.gen_range(0..4) {
+            plan = self.mutate(plan);
+        }
+        let sink = Arc::new(Mutex::new(Coverage::default()));
+        let Some(payoff) = game(AttackerFixed::new(plan.clone()), sink.clone()) else { return false };
+        let reached = std::mem::take(&mut *sink.lock().unwrap());
+        let best = self.corpus.best().map(|x| x.1).unwrap_or(f64::NEG_INFINITY);
+        let new_coverage = reached.iter().any(|pc| !self.coverage.contains(pc));
+        if !new_coverage && payoff <= best { return false }
+        self.coverage.extend(reached);
+        self.corpus.entries.push((plan, payoff));
+        true
+    }
+
+    // run a number of steps, save the corpus after each kept input if a path is given
+    pub fn run(&mut self, steps: usize, game: impl Fn(AttackerFixed, Arc<Mutex<Coverage>>) -> Option<f64>, path: Option<&std::path::Path>) {
+        for _ in 0..steps {
+            if self.step(&game) {
+                if let Some(path) = path { self.corpus.save(path).unwrap(); }
+            }
+        }
+    }
+
+    // apply one random mutation to the nested call structure
+    pub fn mutate(&mut self, mut plan: CallGroup) -> CallGroup {
+        let calls = plan.iter().map(|x| x.len()).sum::<usize>();
+        if plan.is_empty() { plan.push(Vec::new()); }
+        let rng = &mut self.rng;
+        let d = rng.gen_range(0..plan.len());
+        let n = plan[d].len();
+        match rng.gen_range(0..10) {
+            // delete a call
+            0 if n > 0 && calls > 1 => { plan[d].remove(rng.gen_range(0..n)); }
+            // duplicate a call
+            1 if n > 0 => { let c = plan[d][rng.gen_range(0..n)].clone(); plan[d].insert(rng.gen_range(0..=n), c); }
+            // swap two calls at the same depth
+            2 if n > 1 => { plan[d].swap(rng.gen_range(0..n), rng.gen_range(0..n)); }
+            // move a call to another depth, possibly one level deeper than any before
+            3 if n > 0 => {
+                let c = plan[d].remove(rng.gen_range(0..n));
+                let e = rng.gen_range(0..=plan.len());
+                if e == plan.len() { plan.push(Vec::new()); }
+                let m = plan[e].len();
+                plan[e].insert(rng.gen_range(0..=m), c);
+            }
+            // copy a depth level, which makes the reentrancy deeper
+            4 => { let level = plan[d].clone(); plan.insert(d, level); }
+            // drop the deepest level
+            5 if plan.len() > 1 && plan.last().unwrap().len() < calls => { plan.pop(); }
+            // splice with the same depth of another corpus entry
+            6 if !self.corpus.entries.is_empty() => {
+                let other = &self.corpus.entries[rng.gen_range(0..self.corpus.entries.len())].0;
+                if let Some(level) = other.get(d) {
+                    let k = rng.gen_range(0..=n);
+                    plan[d].truncate(k);
+                    plan[d].extend(level.iter().skip(k.min(level.len())).cloned());
+                }
+            }
+            // mutate value
+            7 if n > 0 => {
+                let c = &mut plan[d][rng.gen_range(0..n)];
+                c.1 = match rng.gen_range(0..5) {
+                    0 => U256::ZERO,
+                    1 => c.1 << 1usize,
+                    2 => c.1 >> 1usize,
+                    3 => c.1 ^ (U256::from(1) << rng.gen_range(0..64usize)),
+                    _ => super::random_value(rng),
+                };
+            }
+            // mutate calldata
+            _ if n > 0 => {
+                let c = &mut plan[d][rng.gen_range(0..n)];
+                c.2 = mutate_input(rng, &c.2, &self.addresses);
+            }
+            _ => (),
+        }
+        // trailing empty levels make no calls
+        while plan.len() > 1 && plan.last().unwrap().is_empty() { plan.pop(); }
+        plan
+    }
+}
+
+// mutate calldata, the 4 byte selector is kept
+fn mutate_input(rng: &mut StdRng, input: &Bytes, addresses: &[B160]) -> Bytes {
+    let mut input = input.to_vec();
+    let head = input.len().min(4);
+    let words = (input.len() - head) / 32;
+    match rng.gen_range(0..5) {
+        // flip a bit
+        0 if input.len() > head => {
+            let i = rng.gen_range(head..input.len());
+            input[i] ^= 1 << rng.gen_range(0..8);
+        }
+        // set a byte
+        1 if input.len() > head => {
+            let i = rng.gen_range(head..input.len());
+            input[i] = rng.gen();
+        }
+        // replace an argument word with an interesting value
+        2 | 3 if words > 0 => {
+            let i = head + 32 * rng.gen_range(0..words);
+            let mut word = [0u8; 32];
+            match rng.gen_range(0..4) {
+                0 => (),
+                1 => word[31] = 1,
+                2 => word = [0xff; 32],
+                _ if !addresses.is_empty() => {
+                    word[12..].copy_from_slice(addresses[rng.gen_range(0..addresses.len())].as_bytes());
+                }
+                _ => word[24..].copy_from_slice(&rng.gen::<u64>().to_be_bytes()),
+            }
+            input[i..i + 32].copy_from_slice(&word);
+        }
+        // append or drop a word
+        _ => {
+            if rng.gen_bool(0.5) && words > 0 { input.truncate(input.len() - 32); }
+            else { input.extend_from_slice(&[0u8; 32]); }
+        }
+    }
+    input.into()
+}
@@ -2,18 +2,24 @@ use crate::environment::interfaces::Attacker;
 use revm::interpreter::*;
 use revm::primitives::*;
 
+// calls made at each reentrancy depth, popped from the back
+pub type CallGroup = Vec<Vec<(B160, U256, Bytes)>>;
+
 pub struct AttackerFixed {
-    group: Vec<Vec<(B160, U256, Bytes)>>,
+    group: CallGroup,
 }
 
 impl AttackerFixed {
-    pub fn new(group: Vec<Vec<(B160, U256, Bytes)>>) -> Self {
+    pub fn new(group: CallGroup) -> Self {
         AttackerFixed { group }
     }
+    pub fn group(&self) -> &CallGroup {
+        &self.group
+    }
 }
 
 impl Attacker for AttackerFixed {
-    type State = (usize, Vec<Vec<(B160, U256, Bytes)>>);
+    type State = (usize, CallGroup);
     fn init(&mut self, _contracts: &[(B160, Bytes)]) -> (bool, Self::State) {
         (true, (0, self.group.clone()))
     }
@@ -0,0 +1,242 @@
+use ethers::abi::{Abi, Function, ParamType, StateMutability, Token};
+use rand::prelude::*;
+use revm::interpreter::*;
+use revm::primitives::*;
+use crate::environment::interfaces::Attacker;
+use super::{AttackerFixed, CallGroup};
+
+pub struct FuzzConfig {
+    // upper limit of reentrancy depth that receives calls
+    pub max_depth: usize,
+    // upper limit of calls made each time the attacker is entered
+    pub max_calls: usize,
+    // upper limit of dynamic array / bytes / string length
+    pub max_len: usize,
+    // probability of a plain transfer with empty calldata
+    pub transfer_rate: f64,
+}
+
+impl Default for FuzzConfig {
+    fn default() -> Self {
+        Self { max_depth: 4, max_calls: 4, max_len: 4, transfer_rate: 0.05 }
+    }
+}
+
+// random calls generated from target abis
+// the abi of contracts[i] is abis[i], all calls of a game are drawn at init from the seed
+pub struct AttackerFuzz {
+    abis: Vec<Abi>,
+    config: FuzzConfig,
+    seed: u64,
+    // addresses used for address arguments besides the targets
+    addresses: Vec<B160>,
+    fixed: AttackerFixed,
+}
+
+impl AttackerFuzz {
+    pub fn new(abis: Vec<Abi>, config: FuzzConfig, seed: u64) -> Self {
+        Self { abis, config, seed, addresses: Vec::new(), fixed: AttackerFixed::new(Vec::new()) }
+    }
+    // add addresses for address arguments, e.g. the attacker account
+    pub fn with_addresses(mut self, addresses: Vec<B160>) -> Self {
+        self.addresses.extend(addresses);
+        self
+    }
+    // the calls that this attacker makes against contracts, none without contracts
+    pub fn plan(&self, contracts: &[(B160, Bytes)]) -> CallGroup {
+        if contracts.is_empty() { return Vec::new() }
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut addresses = self.addresses.clone();
+        addresses.extend(contracts.iter().map(|x| x.0));
+        let depth = rng.gen_range(1..=self.config.max_depth.max(1));
+        (0..depth).map(|d| {
+            let calls = rng.gen_range(if d == 0 { 1 } else { 0 }..=self.config.max_calls.max(1));
+            (0..calls).map(|_| self.random_call(&mut rng, contracts, &addresses)).collect()
+        }).collect()
+    }
+    fn random_call(&self, rng: &mut StdRng, contracts: &[(B160, Bytes)], addresses: &[B160]) -> (B160, U256, Bytes) {
+        let i = rng.gen_range(0..contracts.len());
+        let functions = self.abis.get(i).map(|abi| abi.functions().collect::<Vec<_>>()).unwrap_or_default();
+        if functions.is_empty() || rng.gen_bool(self.config.transfer_rate) {
+            return (contracts[i].0, random_value(rng), Bytes::default());
+        }
+        let function = functions[rng.gen_range(0..functions.len())];
+        (contracts[i].0, self.random_value_for(rng, function), random_input(rng, function, addresses, self.config.max_len))
+    }
+    // non payable functions rarely receive value, so that their checks are also exercised
+    fn random_value_for(&self, rng: &mut StdRng, function: &Function) -> U256 {
+        if function.state_mutability == StateMutability::Payable || rng.gen_bool(0.05) {
+            random_value(rng)
+        } else {
+            U256::ZERO
+        }
+    }
+}
+
+impl Attacker for AttackerFuzz {
+    type State = <AttackerFixed as Attacker>::State;
+    fn init(&mut self, contracts: &[(B160, Bytes)]) -> (bool, Self::State) {
+        self.fixed = AttackerFixed::new(self.plan(contracts));
+        self.fixed.init(contracts)
+    }
+    fn check(&self, state: &mut Self::State) -> bool {
+        self.fixed.check(state)
+    }
+    fn make_mal_call(&self, state: &mut Self::State) -> Option<(B160, U256, Bytes)> {
+        self.fixed.make_mal_call(state)
+    }
+    fn take_return(&self, state: &mut Self::State, ret: InstructionResult, gas: Gas, out: Bytes) {
+        self.fixed.take_return(state, ret, gas, out)
+    }
+}
+
+// plans that were interesting in some game, with their payoff
+#[derive(Default)]
+pub struct FuzzCorpus {
+    pub entries: Vec<(CallGroup, f64)>,
+}
+
+impl FuzzCorpus {
+    // keep a plan if it has positive payoff or beats every plan in the corpus, the first plan is always kept
+    pub fn consider(&mut self, plan: CallGroup, payoff: f64) -> bool {
+        let best = self.best().map(|x| x.1).unwrap_or(f64::NEG_INFINITY);
+        let interesting = self.entries.is_empty() || payoff > best || (payoff > 0.0 && !self.entries.iter().any(|x| x.0 == plan));
+        if interesting { self.entries.push((plan, payoff)); }
+        interesting
+    }
+    pub fn best(&self) -> Option<&(CallGroup, f64)> {
+        self.entries.iter().max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+    // save as text, each entry is `entry <payoff> <depths>` followed by `<depth> <address> <value> <input>` per call
+    pub fn save(&self, path: &std::path::Path) -> std::io::Result<()> {
+        use std::io::Write;
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        for (plan, payoff) in self.entries.iter() {
+            writeln!(file, "entry {payoff} {}", plan.len())?;
+            for (depth, calls) in plan.iter().enumerate() {
+                for (address, value, input) in calls {
+                    writeln!(file, "{depth} {} {value:#x} 0x{}", hex::encode(address), hex::encode(input))?;
+                }
+            }
+        }
+        file.flush()
+    }
+    // load a corpus written by save
+    pub fn load(path: &std::path::Path) -> std::io::Result<Self> {
+        let bad = |line: usize| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("bad corpus line {}", line + 1));
+        let mut corpus = Self::default();
+        for (nr, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            match words[..] {
+                [] => continue,
+                ["entry", payoff, depths] => {
+                    let payoff = payoff.parse().map_err(|_| bad(nr))?;
+                    let depths = depths.parse().map_err(|_| bad(nr))?;
+                    corpus.entries.push((vec![Vec::new(); depths], payoff));
+                }
+                [depth, address, value, input] => {
+                    let depth = depth.parse::<usize>().map_err(|_| bad(nr))?;
+                    let address = hex::decode(address.trim_start_matches("0x")).map_err(|_| bad(nr))?;
+                    let value = U256::from_str_radix(value.trim_start_matches("0x"), 16).map_err(|_| bad(nr))?;
+                    let input = hex::decode(input.trim_start_matches("0x")).map_err(|_| bad(nr))?;
+                    let Some((plan, _)) = corpus.entries.last_mut() else { return Err(bad(nr)) };
+                    let Some(calls) = plan.get_mut(depth) else { return Err(bad(nr)) };
+                    if address.len() != 20 { return Err(bad(nr)) }
+                    calls.push((B160::from_slice(&address), value, input.into()));
+                }
+                _ => return Err(bad(nr)),
+            }
+        }
+        Ok(corpus)
+    }
+}
+
+// play one seeded game per seed, game returns the payoff of the attacker or None if it fails
+// contracts are the targets as seen by the attacker in every game
+pub fn fuzz_campaign(
+    make: impl Fn(u64) -> AttackerFuzz,
+    contracts: &[(B160, Bytes)],
+    seeds: std::ops::Range<u64>,
+    corpus: &mut FuzzCorpus,
+    game: impl Fn(AttackerFuzz) -> Option<f64>,
+) {
+    for seed in seeds {
+        let attacker = make(seed);
+        let plan = attacker.plan(contracts);
+        if let Some(payoff) = game(attacker) {
+            corpus.consider(plan, payoff);
+        }
+    }
+}
+
+// values biased to zero, small amounts and large amounts
+pub fn random_value(rng: &mut impl Rng) -> U256 {
+    match rng.gen_range(0..4) {
+        0 => U256::ZERO,
+        1 => U256::from(rng.gen_range(1..=u16::MAX as u64)),
+        2 => U256::from(rng.gen_range(1..=u32::MAX as u64)),
+        _ => U256::from(rng.gen_range(1..=u64::MAX / 4)),
+    }
+}
+
+// calldata of a function with random arguments
+pub fn random_input(rng: &mut impl Rng, function: &Function, addresses: &[B160], max_len: usize) -> Bytes {
+    let tokens = function.inputs.iter()
+        .map(|param| random_token(rng, &param.kind, addresses, max_len))
+        .collect::<Vec<_>>();
+    function.encode_input(&tokens).unwrap().into()
+}
+
+// a random token of the given type
+pub fn random_token(rng: &mut impl Rng, kind: &ParamType, addresses: &[B160], max_len: usize) -> Token {
+    use ethers::types::U256 as EU256;
+    match kind {
+        ParamType::Address => {
+            if !addresses.is_empty() && rng.gen_bool(0.8) {
+                Token::Address(addresses[rng.gen_range(0..addresses.len())].into())
+            } else {
+                Token::Address(ethers::types::H160::from(rng.gen::<[u8; 20]>()))
+            }
+        }
+        ParamType::Uint(n) => Token::Uint(uint(rng, *n)),
+        ParamType::Int(n) => {
+            // sign extend n bits to 256 bits
+            let x = uint(rng, *n);
+            let negative = *n < 256 && x.bit(n - 1);
+            Token::Int(if negative { x | !((EU256::one() << *n) - 1) } else { x })
+        }
+        ParamType::Bool => Token::Bool(rng.gen()),
+        ParamType::String => {
+            let len = rng.gen_range(0..=max_len * 8);
+            Token::String((0..len).map(|_| rng.gen_range(b' '..=b'~') as char).collect())
+        }
+        ParamType::Bytes => {
+            let len = rng.gen_range(0..=max_len * 32);
+            Token::Bytes((0..len).map(|_| rng.gen()).collect())
+        }
+        ParamType::FixedBytes(n) => Token::FixedBytes((0..*n).map(|_| rng.gen()).collect()),
+        ParamType::Array(kind) => {
+            let len = rng.gen_range(0..=max_len);
+            Token::Array((0..len).map(|_| random_token(rng, kind, addresses, max_len)).collect())
+        }
+        ParamType::FixedArray(kind, n) => {
+            Token::FixedArray((0..*n).map(|_| random_token(rng, kind, addresses, max_len)).collect())
+        }
+        ParamType::Tuple(kinds) => {
+            Token::Tuple(kinds.iter().map(|kind| random_token(rng, kind, addresses, max_len)).collect())
+        }
+    }
+}
+
+// random integer with n bits, biased to boundary values
+fn uint(rng: &mut impl Rng, n: usize) -> ethers::types::U256 {
+    use ethers::types::U256 as EU256;
+    let mask = if n >= 256 { EU256::MAX } else { (EU256::one() << n) - 1 };
+    let x = match rng.gen_range(0..4) {
+        0 => EU256::zero(),
+        1 => EU256::from(rng.gen_range(0..1024u64)),
+        2 => mask,
+        _ => EU256::from_big_endian(&rng.gen::<[u8; 32]>()),
+    };
+    x & mask
+}
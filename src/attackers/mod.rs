@@ -1,4 +1,16 @@
+mod assembly;
+pub use assembly::*;
+mod coverage;
+pub use coverage::*;
 mod fixed;
 pub use fixed::*;
+mod fuzz;
+pub use fuzz::*;
 mod neural;
-pub use neural::*;
\ No newline at end of file
+pub use neural::*;
+mod replay;
+pub use replay::*;
+#[cfg(feature = "neural")]
+mod transformer;
+#[cfg(feature = "neural")]
+pub use transformer::*;
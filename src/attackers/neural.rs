@@ -1,23 +1,55 @@
 use revm::primitives::*;
 use revm::interpreter::*;
+use rand::Rng;
 use crate::environment::interfaces::Attacker;
 
+#[derive(Clone)]
 pub struct AttackerNeural {
-    script_make_call: Vec<u32>,
-    script_proj_data: Vec<u32>,
-    proj_skip_call: [u32; 1024],
-    proj_value: [u32; 1024],
-    proj_contract: [u32; 1024],
-    contracts: Vec<B160>,
-    script_take_return: Vec<u32>,
-    script_check: Vec<u32>,
-    proj_check: [u32; 1024],
+    pub(crate) script_make_call: Vec<u32>,
+    pub(crate) script_proj_data: Vec<u32>,
+    pub(crate) proj_skip_call: [u32; 1024],
+    pub(crate) proj_value: [u32; 1024],
+    pub(crate) proj_contract: [u32; 1024],
+    pub(crate) contracts: Vec<B160>,
+    pub(crate) script_take_return: Vec<u32>,
+    pub(crate) script_check: Vec<u32>,
+    pub(crate) proj_check: [u32; 1024],
+}
+
+impl AttackerNeural {
+    // sample an attacker with random scripts of the given length and random projections
+    pub fn random(rng: &mut impl Rng, script_len: usize) -> Self {
+        let mut script = || (0..script_len).map(|_| rng.gen()).collect::<Vec<u32>>();
+        let (script_make_call, script_proj_data) = (script(), script());
+        let (script_take_return, script_check) = (script(), script());
+        let mut proj = || { let mut p = [0u32; 1024]; rng.fill(&mut p[..]); p };
+        Self {
+            script_make_call, script_proj_data, script_take_return, script_check,
+            proj_skip_call: proj(), proj_value: proj(), proj_contract: proj(), proj_check: proj(),
+            contracts: Vec::new(),
+        }
+    }
+    // scripts of this attacker, in a fixed order
+    pub fn scripts(&self) -> [&Vec<u32>; 4] {
+        [&self.script_make_call, &self.script_proj_data, &self.script_take_return, &self.script_check]
+    }
+    pub fn scripts_mut(&mut self) -> [&mut Vec<u32>; 4] {
+        [&mut self.script_make_call, &mut self.script_proj_data, &mut self.script_take_return, &mut self.script_check]
+    }
+    // projections of this attacker, in a fixed order
+    pub fn projs(&self) -> [&[u32; 1024]; 4] {
+        [&self.proj_skip_call, &self.proj_value, &self.proj_contract, &self.proj_check]
+    }
+    pub fn projs_mut(&mut self) -> [&mut [u32; 1024]; 4] {
+        [&mut self.proj_skip_call, &mut self.proj_value, &mut self.proj_contract, &mut self.proj_check]
+    }
 }
 
 impl Attacker for AttackerNeural {
     type State = [u32; 1024];
     fn init(&mut self, contracts: &[(B160, Bytes)]) -> (bool, Self::State) {
-        todo!()
+        self.contracts = contracts.iter().map(|x| x.0).collect();
+        (true, [0u32; 1024])
     }
     fn check(&self, state: &mut Self::State) -> bool {
         apply(state, Bytes::default(), &self.script_check);
@@ -26,7 +58,7 @@ impl Attacker for AttackerNeural {
     fn make_mal_call(&self, state: &mut Self::State) -> Option<(B160, U256, Bytes)> {
         let skip = cast_bool(state, &self.proj_skip_call);
         apply(state, Bytes::default(), &self.script_make_call);
-        if skip { None }
+        if skip || self.contracts.is_empty() { None }
         else {
             let x = cast_byte(2, state, &self.proj_contract);
             let contract = self.contracts[(x[0] as usize * 256 + x[1] as usize) % self.contracts.len()];
@@ -44,13 +76,16 @@ impl Attacker for AttackerNeural {
     }
 }
 
+// pack the bits of a selected by b into at most m bytes
+// unselected bits are skipped, and m is capped at the size of a so that m = usize::MAX takes every selected bit
 pub fn cast_byte<const N: usize>(m: usize, a: &[u32; N], b: &[u32; N]) -> Vec<u8> {
-    let mut bytes = vec![0u8; m];
-    let mut k = 0;
-    for i in 0..m*8 {
-        if k >= N * 32 { break; }
-        if b[k / 32] >> k % 32 & 1 == 0 { k += 1; }
+    let mut bytes = vec![0u8; m.min(N * 4)];
+    let mut i = 0;
+    for k in 0..N*32 {
+        if i >= bytes.len() * 8 { break; }
+        if b[k / 32] >> k % 32 & 1 == 0 { continue; }
         bytes[i / 8] |= ((a[k / 32] >> k % 32 & 1) as u8) << i % 8;
+        i += 1;
     }
     return bytes;
 }
@@ -67,10 +102,13 @@ pub fn apply<const N: usize>(state: &mut [u32; N], bytes: Bytes, script: &[u32])
     let mut script = script.into_iter();
     let len = state.len();
     assert!(len.count_ones() == 1);
-    let mask = 1 << len.trailing_zeros() - 1;
+    // every word of the state is addressable
+    let mask = len - 1;
     let get = move |script: &mut std::slice::Iter<'_, u32>| {
         script.next().copied().unwrap_or(0)
     };
+    // reading from empty bytes gives zero
+    let byte = |y: u32| if bytes.is_empty() { 0 } else { bytes[y as usize % bytes.len()] as u32 };
     let mut x = get(&mut script);
     let mut y = get(&mut script);
     while let Some(instr) = script.next() {
@@ -78,8 +116,10 @@ pub fn apply<const N: usize>(state: &mut [u32; N], bytes: Bytes, script: &[u32])
             0 => { y = y.overflowing_add(x).0 }
             1 => { y = y.overflowing_sub(x).0 }
             2 => { y = y.overflowing_mul(x).0 }
-            3 => { y = y.overflowing_div(x).0 }
-            4 => { y = y.overflowing_rem(x).0 }
+            // division by zero gives zero instead of panicking in the middle of a game
+            3 => { y = y.checked_div(x).unwrap_or(0) }
+            4 => { y = y.checked_rem(x).unwrap_or(0) }
+            5 => {}
             6 => { y = y.overflowing_shl(x).0 }
             7 => { y = y.overflowing_shr(x).0 }
             8 => { y = y & x }
@@ -89,23 +129,23 @@ pub fn apply<const N: usize>(state: &mut [u32; N], bytes: Bytes, script: &[u32])
             12 => { std::mem::swap(&mut x, &mut y); }
             13 => { state[x as usize & mask] = y; }
             14 => { y = state[y as usize & mask]; }
-            15 => { y = bytes[y as usize % bytes.len()] as u32; }
-            16 => { y = bytes[y as usize % bytes.len()] as u32 * 256; }
-            17 => { y = bytes[y as usize % bytes.len()] as u32 * 256 * 256; }
-            18 => { y = bytes[y as usize % bytes.len()] as u32 * 256 * 256 * 256; }
+            15 => { y = byte(y); }
+            16 => { y = byte(y) * 256; }
+            17 => { y = byte(y) * 256 * 256; }
+            18 => { y = byte(y) * 256 * 256 * 256; }
             _ => unreachable!(),
         }
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "neural"))]
 mod tests {
     use tch::{Kind, Device};
 
     #[test]
     fn test_tch_works() {
-        let a = tch::Tensor::rand([1,5], (Kind::Double, Device::Cuda(0)));
-        let b = tch::Tensor::rand([1,5], (Kind::Double, Device::Cuda(0)));
+        let a = tch::Tensor::rand([1,5], (Kind::Double, Device::Cpu));
+        let b = tch::Tensor::rand([1,5], (Kind::Double, Device::Cpu));
         println!("{} \n+ {} \n= {}", a, b, &a + &b);
     }
 }
\ No newline at end of file
@@ -0,0 +1,204 @@
+use std::sync::{Arc, Mutex};
+use revm::interpreter::*;
+use revm::primitives::*;
+use crate::environment::interfaces::Attacker;
+
+// what the attacker did and saw, depth is the reentrancy depth of the attacker
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Call { depth: usize, address: B160, value: U256, input: Bytes },
+    // status is the debug name of the instruction result
+    Return { depth: usize, status: String, out: Bytes },
+    // the attacker leaves depth, ok is the result of check
+    Exit { depth: usize, ok: bool },
+}
+
+// a game as seen by the attacker, in the order things happened
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recording {
+    pub events: Vec<Event>,
+}
+
+impl Recording {
+    // the calls made at each depth, in the format of AttackerFixed
+    pub fn call_group(&self) -> super::CallGroup {
+        let mut group: super::CallGroup = Vec::new();
+        for event in self.events.iter() {
+            let Event::Call { depth, address, value, input } = event else { continue };
+            if group.len() <= *depth { group.resize(depth + 1, Vec::new()); }
+            group[*depth].insert(0, (*address, *value, input.clone()));
+        }
+        group
+    }
+    // save as text, one event per line
+    pub fn save(&self, path: &std::path::Path) -> std::io::Result<()> {
+        use std::io::Write;
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        for event in self.events.iter() {
+            match event {
+                Event::Call { depth, address, value, input } =>
+                    writeln!(file, "call {depth} 0x{} {value:#x} 0x{}", hex::encode(address), hex::encode(input))?,
+                Event::Return { depth, status, out } =>
+                    writeln!(file, "return {depth} {status} 0x{}", hex::encode(out))?,
+                Event::Exit { depth, ok } =>
+                    writeln!(file, "exit {depth} {ok}")?,
+            }
+        }
+        file.flush()
+    }
+    // load a recording written by save
+    pub fn load(path: &std::path::Path) -> std::io::Result<Self> {
+        let bad = |line: usize| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("bad recording line {}", line + 1));
+        let bytes = |x: &str| hex::decode(x.trim_start_matches("0x")).ok();
+        let mut events = Vec::new();
+        for (nr, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let event = match words[..] {
+                [] => continue,
+                ["call", depth, address, value, input] => Event::Call {
+                    depth: depth.parse().map_err(|_| bad(nr))?,
+                    address: bytes(address).filter(|x| x.len() == 20).map(|x| B160::from_slice(&x)).ok_or_else(|| bad(nr))?,
+                    value: U256::from_str_radix(value.trim_start_matches("0x"), 16).map_err(|_| bad(nr))?,
+                    input: bytes(input).ok_or_else(|| bad(nr))?.into(),
+                },
+                ["return", depth, status, out] => Event::Return {
+                    depth: depth.parse().map_err(|_| bad(nr))?,
+                    status: status.to_string(),
+                    out: bytes(out).ok_or_else(|| bad(nr))?.into(),
+                },
+                ["exit", depth, ok] => Event::Exit {
+                    depth: depth.parse().map_err(|_| bad(nr))?,
+                    ok: ok.parse().map_err(|_| bad(nr))?,
+                },
+                _ => return Err(bad(nr)),
+            };
+            events.push(event);
+        }
+        Ok(Self { events })
+    }
+}
+
+// wraps an attacker and records its game into a shared recording
+pub struct AttackerRecord<A: Attacker> {
+    attacker: A,
+    recording: Arc<Mutex<Recording>>,
+}
+
+impl<A: Attacker> AttackerRecord<A> {
+    pub fn new(attacker: A) -> (Self, Arc<Mutex<Recording>>) {
+        let recording = Arc::new(Mutex::new(Recording::default()));
+        (Self { attacker, recording: recording.clone() }, recording)
+    }
+}
+
+impl<A: Attacker> Attacker for AttackerRecord<A> {
+    // state of the wrapped attacker, current depth
+    type State = (A::State, usize);
+    fn init(&mut self, contracts: &[(B160, Bytes)]) -> (bool, Self::State) {
+        self.recording.lock().unwrap().events.clear();
+        let (is_malicious, state) = self.attacker.init(contracts);
+        (is_malicious, (state, 0))
+    }
+    fn make_mal_call(&self, state: &mut Self::State) -> Option<(B160, U256, Bytes)> {
+        let (address, value, input) = self.attacker.make_mal_call(&mut state.0)?;
+        self.recording.lock().unwrap().events.push(Event::Call { depth: state.1, address, value, input: input.clone() });
+        state.1 += 1;
+        Some((address, value, input))
+    }
+    fn take_return(&self, state: &mut Self::State, ret: InstructionResult, gas: Gas, out: Bytes) {
+        state.1 -= 1;
+        self.recording.lock().unwrap().events.push(Event::Return { depth: state.1, status: format!("{ret:?}"), out: out.clone() });
+        self.attacker.take_return(&mut state.0, ret, gas, out)
+    }
+    fn check(&self, state: &mut Self::State) -> bool {
+        let ok = self.attacker.check(&mut state.0);
+        self.recording.lock().unwrap().events.push(Event::Exit { depth: state.1, ok });
+        ok
+    }
+}
+
+// a difference between a replayed game and its recording, index points into the recording
+#[derive(Clone, Debug, PartialEq)]
+pub enum Divergence {
+    // a recorded event was skipped, because the game went elsewhere
+    Skipped { index: usize, event: Event },
+    // a recorded return differs from the replayed one
+    Return { index: usize, status: String, out: Bytes },
+    // the attacker was entered at a depth where the recording has nothing left
+    Unexpected { depth: usize },
+}
+
+// replays a recording call by call, divergences are reported into a shared list
+pub struct AttackerReplay {
+    recording: Recording,
+    divergences: Arc<Mutex<Vec<Divergence>>>,
+}
+
+impl AttackerReplay {
+    pub fn new(recording: Recording) -> (Self, Arc<Mutex<Vec<Divergence>>>) {
+        let divergences = Arc::new(Mutex::new(Vec::new()));
+        (Self { recording, divergences: divergences.clone() }, divergences)
+    }
+    // move cursor to the next event at depth accepted by select, reporting skipped events
+    fn seek(&self, cursor: &mut usize, depth: usize, select: impl Fn(&Event) -> bool) -> Option<usize> {
+        let events = &self.recording.events;
+        let found = (*cursor..events.len()).find(|&i| {
+            let d = match &events[i] { Event::Call { depth, .. } | Event::Return { depth, .. } | Event::Exit { depth, .. } => *depth };
+            d == depth && select(&events[i])
+        });
+        let found = found?;
+        let mut divergences = self.divergences.lock().unwrap();
+        for index in *cursor..found {
+            divergences.push(Divergence::Skipped { index, event: events[index].clone() });
+        }
+        *cursor = found + 1;
+        Some(found)
+    }
+    // report recorded events that were never replayed
+    fn finish(&self, cursor: usize) {
+        let mut divergences = self.divergences.lock().unwrap();
+        for index in cursor..self.recording.events.len() {
+            divergences.push(Divergence::Skipped { index, event: self.recording.events[index].clone() });
+        }
+    }
+}
+
+impl Attacker for AttackerReplay {
+    // cursor into the recording, current depth
+    type State = (usize, usize);
+    fn init(&mut self, _contracts: &[(B160, Bytes)]) -> (bool, Self::State) {
+        self.divergences.lock().unwrap().clear();
+        (true, (0, 0))
+    }
+    fn make_mal_call(&self, state: &mut Self::State) -> Option<(B160, U256, Bytes)> {
+        let (cursor, depth) = state;
+        let Some(i) = self.seek(cursor, *depth, |e| matches!(e, Event::Call { .. } | Event::Exit { .. })) else {
+            self.divergences.lock().unwrap().push(Divergence::Unexpected { depth: *depth });
+            return None;
+        };
+        match &self.recording.events[i] {
+            Event::Call { address, value, input, .. } => {
+                *depth += 1;
+                Some((*address, *value, input.clone()))
+            }
+            // leave the exit for check
+            _ => { *cursor = i; None }
+        }
+    }
+    fn take_return(&self, state: &mut Self::State, ret: InstructionResult, _gas: Gas, out: Bytes) {
+        let (cursor, depth) = state;
+        *depth -= 1;
+        let status = format!("{ret:?}");
+        let Some(i) = self.seek(cursor, *depth, |e| matches!(e, Event::Return { .. })) else { return };
+        if self.recording.events[i] != (Event::Return { depth: *depth, status: status.clone(), out: out.clone() }) {
+            self.divergences.lock().unwrap().push(Divergence::Return { index: i, status, out });
+        }
+    }
+    fn check(&self, state: &mut Self::State) -> bool {
+        let (cursor, depth) = state;
+        let Some(i) = self.seek(cursor, *depth, |e| matches!(e, Event::Exit { .. })) else { return true };
+        let Event::Exit { ok, .. } = self.recording.events[i] else { unreachable!() };
+        if *depth == 0 { self.finish(*cursor); }
+        ok
+    }
+}
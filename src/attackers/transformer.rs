@@ -0,0 +1,235 @@
+use std::sync::{Arc, Mutex};
+use ethers::abi::Abi;
+use rand::prelude::*;
+use revm::interpreter::*;
+use revm::primitives::*;
+use tch::{Kind, Tensor};
+use crate::environment::interfaces::Attacker;
+use crate::neural::*;
+use super::CallGroup;
+
+pub struct TransformerConfig {
+    pub temperature: f64,
+    // upper limit of the bit length of values
+    pub max_value_bits: usize,
+    // upper limit of significant bytes of an argument word
+    pub max_word_bytes: usize,
+    // upper limit of argument words of a call without abi
+    pub max_words: usize,
+    // upper limit of bytecode tokens per target in the prefix
+    pub code_len: usize,
+    pub seed: u64,
+}
+
+impl Default for TransformerConfig {
+    fn default() -> Self {
+        Self { temperature: 1.0, max_value_bits: 64, max_word_bytes: 8, max_words: 4, code_len: 256, seed: 0 }
+    }
+}
+
+// a sampled token, position is its index in the episode tokens and allowed are the tokens it was sampled from
+#[derive(Clone, Debug)]
+pub struct Choice {
+    pub position: usize,
+    pub token: i64,
+    pub log_prob: f64,
+    pub allowed: Vec<i64>,
+}
+
+// the last game of an attacker, all its tokens and the choices among them
+#[derive(Clone, Debug, Default)]
+pub struct Episode {
+    pub tokens: Vec<i64>,
+    pub choices: Vec<Choice>,
+}
+
+// samples each call token by token from a transformer over the target bytecode and the game history
+// a call is CALL, target, value bucket, selector and argument words ended by STOP, or STOP alone to make no call
+pub struct AttackerTransformer {
+    model: Arc<Model>,
+    config: TransformerConfig,
+    // abis of the targets, selectors and argument counts are taken from them if any
+    abis: Vec<Abi>,
+    attacker: B160,
+    encoder: Option<TraceEncoder>,
+    // argument words of each selector of the encoder, if known
+    arguments: Vec<Option<usize>>,
+    episode: Arc<Mutex<Episode>>,
+    games: u64,
+    // calls to demonstrate instead of sampling, in the format of AttackerFixed
+    teacher: Option<CallGroup>,
+}
+
+impl AttackerTransformer {
+    // the model must have vocab_size VOCAB
+    pub fn new(model: Arc<Model>, abis: Vec<Abi>, attacker: B160, config: TransformerConfig) -> (Self, Arc<Mutex<Episode>>) {
+        assert_eq!(model.config.vocab_size, VOCAB);
+        let episode = Arc::new(Mutex::new(Episode::default()));
+        let attacker = Self { model, config, abis, attacker, encoder: None, arguments: Vec::new(), episode: episode.clone(), games: 0, teacher: None };
+        (attacker, episode)
+    }
+    // make the calls of group and record them as choices of the policy, for behaviour cloning
+    // the calls are made as given, their tokens are what the policy can express of them
+    pub fn imitate(mut self, group: CallGroup) -> Self {
+        self.teacher = Some(group);
+        self
+    }
+    // tokens of a call with the longest arguments
+    fn call_len(&self) -> usize {
+        let words = self.arguments.iter().flatten().copied().chain([self.config.max_words]).max().unwrap();
+        5 + words * (1 + self.config.max_word_bytes) + 1
+    }
+    // sample one of allowed after tokens and append it, choices of one token are not recorded
+    // tokens after those in cache are fed to the model, so each token is seen once per game
+    // a forced token is recorded with log_prob 0 without running the model
+    fn sample(&self, tokens: &mut Vec<i64>, rng: &mut StdRng, cache: &mut ModelCache, forced: Option<i64>, allowed: Vec<i64>) -> i64 {
+        if allowed.len() == 1 {
+            tokens.push(allowed[0]);
+            return allowed[0];
+        }
+        if let Some(token) = forced {
+            assert!(allowed.contains(&token));
+            self.episode.lock().unwrap().choices.push(Choice { position: tokens.len(), token, log_prob: 0.0, allowed });
+            tokens.push(token);
+            return token;
+        }
+        let device = self.model.device();
+        let log_probs = tch::no_grad(|| {
+            let new = &tokens[cache.len() as usize..];
+            let logits = self.model.forward_cached(&Tensor::from_slice(new).reshape([1, new.len() as i64]).to_device(device), cache);
+            let logits = logits.get(0).get(new.len() as i64 - 1).index_select(0, &Tensor::from_slice(&allowed).to_device(device));
+            (logits / self.config.temperature).log_softmax(-1, Kind::Double)
+        });
+        let log_probs = Vec::<f64>::try_from(&log_probs).unwrap();
+        let mut u = rng.gen::<f64>();
+        let i = log_probs.iter().position(|p| { u -= p.exp(); u < 0.0 }).unwrap_or(allowed.len() - 1);
+        let token = allowed[i];
+        self.episode.lock().unwrap().choices.push(Choice { position: tokens.len(), token, log_prob: log_probs[i], allowed });
+        tokens.push(token);
+        token
+    }
+    // forced are the tokens of a demonstrated call, without them the call is sampled
+    fn sample_call(&self, tokens: &mut Vec<i64>, rng: &mut StdRng, cache: &mut ModelCache, forced: Option<Vec<i64>>) -> Option<(B160, U256, Bytes)> {
+        let encoder = self.encoder.as_ref()?;
+        if encoder.targets.is_empty() || tokens.len() + self.call_len() > self.model.config.block_size as usize { return None }
+        let mut forced = forced.map(|x| x.into_iter());
+        let mut next = || forced.as_mut().and_then(|x| x.next());
+        let start = tokens.len();
+        if self.sample(tokens, rng, cache, next(), vec![CALL, STOP]) == STOP { return None }
+        let targets = (0..encoder.targets.len() as i64).map(|i| TARGET + i).collect::<Vec<_>>();
+        self.sample(tokens, rng, cache, next(), targets.clone());
+        self.sample(tokens, rng, cache, next(), (0..=self.config.max_value_bits as i64).map(|b| VALUE + b).collect());
+        let mut selectors = (0..encoder.selectors.len() as i64).map(|i| SELECTOR_INDEX + i).collect::<Vec<_>>();
+        selectors.push(NO_SELECTOR);
+        let selector = self.sample(tokens, rng, cache, next(), selectors);
+        if selector != NO_SELECTOR {
+            let mut words = vec![WORD_ZERO, WORD_ATTACKER];
+            words.extend(targets);
+            words.extend((1..=self.config.max_word_bytes as i64).map(|n| WORD_BYTES + n));
+            let count = self.arguments[(selector - SELECTOR_INDEX) as usize];
+            for w in 0..count.unwrap_or(self.config.max_words) {
+                let mut allowed = words.clone();
+                if count.is_none() && w > 0 { allowed.push(STOP); }
+                let word = self.sample(tokens, rng, cache, next(), allowed);
+                if word == STOP { break }
+                if word > WORD_BYTES {
+                    for _ in 0..word - WORD_BYTES {
+                        self.sample(tokens, rng, cache, next(), (0..256).map(|b| IMMEDIATE + b).collect());
+                    }
+                }
+            }
+            if tokens.last() != Some(&STOP) { tokens.push(STOP); }
+        }
+        encoder.decode_call(&tokens[start..])
+    }
+    // the tokens sample_call would choose for a call, None if the call cannot be expressed
+    // values are bucketed by bit length, missing arguments are zero and extra ones are dropped
+    fn demonstrate(&self, call: &(B160, U256, Bytes)) -> Option<Vec<i64>> {
+        let encoder = self.encoder.as_ref()?;
+        let (address, value, input) = call;
+        let target = encoder.targets.iter().position(|t| t == address)? as i64;
+        if value.bit_len() > self.config.max_value_bits { return None }
+        let mut tokens = vec![CALL, TARGET + target, VALUE + value.bit_len() as i64];
+        if input.len() < 4 {
+            tokens.push(NO_SELECTOR);
+            return Some(tokens);
+        }
+        let selector = encoder.selectors.iter().position(|s| s[..] == input[..4])?;
+        tokens.push(SELECTOR_INDEX + selector as i64);
+        let words = input[4..].chunks(32).map(|x| {
+            let mut word = [0u8; 32];
+            word[..x.len()].copy_from_slice(x);
+            word
+        }).collect::<Vec<_>>();
+        let count = self.arguments[selector];
+        for w in 0..count.unwrap_or(words.len().clamp(1, self.config.max_words)) {
+            let word = words.get(w).copied().unwrap_or([0u8; 32]);
+            let address = Some(B160::from_slice(&word[12..])).filter(|_| word[..12].iter().all(|b| *b == 0));
+            if word.iter().all(|b| *b == 0) {
+                tokens.push(WORD_ZERO);
+            } else if address == Some(self.attacker) {
+                tokens.push(WORD_ATTACKER);
+            } else if let Some(i) = encoder.targets.iter().position(|t| Some(*t) == address) {
+                tokens.push(TARGET + i as i64);
+            } else {
+                let n = 32 - word.iter().take_while(|b| **b == 0).count();
+                if n > self.config.max_word_bytes { return None }
+                tokens.push(WORD_BYTES + n as i64);
+                tokens.extend(word[32 - n..].iter().map(|b| IMMEDIATE + *b as i64));
+            }
+        }
+        if count.is_none() && words.len() < self.config.max_words { tokens.push(STOP); }
+        Some(tokens)
+    }
+}
+
+impl Attacker for AttackerTransformer {
+    // tokens so far, random state of sampling, cache of the tokens seen by the model
+    // and the depth and remaining calls of the teacher, if any
+    type State = (Vec<i64>, StdRng, ModelCache, Option<(usize, CallGroup)>);
+    fn init(&mut self, contracts: &[(B160, Bytes)]) -> (bool, Self::State) {
+        let mut encoder = TraceEncoder::new(contracts, self.attacker);
+        self.arguments = vec![None; encoder.selectors.len()];
+        if !self.abis.is_empty() {
+            let functions = self.abis.iter().flat_map(|abi| abi.functions()).take(MAX_SELECTORS).collect::<Vec<_>>();
+            encoder.selectors = functions.iter().map(|f| f.short_signature()).collect();
+            self.arguments = functions.iter().map(|f| Some(f.inputs.len())).collect();
+        }
+        self.encoder = Some(encoder);
+        let tokens = code_prefix(contracts, self.config.code_len);
+        *self.episode.lock().unwrap() = Episode { tokens: tokens.clone(), choices: Vec::new() };
+        let rng = StdRng::seed_from_u64(self.config.seed.wrapping_add(self.games));
+        self.games += 1;
+        let teacher = self.teacher.clone().map(|group| (0, group));
+        (true, (tokens, rng, ModelCache::default(), teacher))
+    }
+    fn make_mal_call(&self, state: &mut Self::State) -> Option<(B160, U256, Bytes)> {
+        let (tokens, rng, cache, teacher) = state;
+        let Some((depth, group)) = teacher else {
+            let call = self.sample_call(tokens, rng, cache, None);
+            self.episode.lock().unwrap().tokens = tokens.clone();
+            return call;
+        };
+        // the demonstration ends at the first call that cannot be expressed
+        let call = group.get_mut(*depth).and_then(|calls| calls.pop());
+        let forced = call.as_ref().and_then(|call| self.demonstrate(call));
+        if call.is_some() && forced.is_none() { group.clear(); }
+        let made = self.sample_call(tokens, rng, cache, Some(forced.unwrap_or_else(|| vec![STOP])));
+        self.episode.lock().unwrap().tokens = tokens.clone();
+        let call = call.filter(|_| made.is_some());
+        if call.is_some() { *depth += 1; }
+        call
+    }
+    fn take_return(&self, state: &mut Self::State, ret: InstructionResult, _gas: Gas, out: Bytes) {
+        let Some(encoder) = self.encoder.as_ref() else { return };
+        let (tokens, _, _, teacher) = state;
+        if let Some((depth, _)) = teacher { *depth -= 1; }
+        let mut event = encoder.encode_event(&TraceEvent::Return { status: ret, out });
+        // outputs that no longer fit are cut, leaving room for the next call
+        let room = (self.model.config.block_size as usize).saturating_sub(tokens.len() + self.call_len());
+        event.truncate(room);
+        tokens.extend(event);
+        self.episode.lock().unwrap().tokens = tokens.clone();
+    }
+    fn check(&self, _state: &mut Self::State) -> bool { true }
+}
@@ -0,0 +1,83 @@
+use ethers::abi::{Abi, Function, ParamType, StateMutability, Token};
+use rand::prelude::*;
+use revm::primitives::*;
+use crate::attackers::{AttackerFixed, random_token};
+use crate::environment::{Environment, interfaces::Defender};
+
+// valid calls of benign users generated from target abis
+// payable functions are deposits, other state changing functions with an amount are withdrawals
+// within what the user deposited, view functions are queries about the user
+pub struct BenignWorkload {
+    abis: Vec<Abi>,
+    rng: StdRng,
+}
+
+impl BenignWorkload {
+    // the abi of targets[i] is abis[i]
+    pub fn new(abis: Vec<Abi>, seed: u64) -> Self {
+        Self { abis, rng: StdRng::seed_from_u64(seed) }
+    }
+    // a sequence of calls made by user
+    pub fn script(&mut self, user: B160, targets: &[B160], calls: usize) -> Vec<(B160, U256, Bytes)> {
+        // amount deposited by the user into each target
+        let mut deposits = vec![0u64; targets.len()];
+        let mut script = Vec::new();
+        // give up on targets without usable functions
+        for _ in 0..calls * 8 {
+            if script.len() >= calls || targets.is_empty() { break }
+            let i = self.rng.gen_range(0..targets.len());
+            let Some(abi) = self.abis.get(i) else { continue };
+            let functions = abi.functions().collect::<Vec<_>>();
+            let Some(function) = functions.choose(&mut self.rng) else { continue };
+            let call = match function.state_mutability {
+                StateMutability::View | StateMutability::Pure => {
+                    let amount = U256::from(self.rng.gen_range(0..1000u64));
+                    (targets[i], U256::ZERO, input(&mut self.rng, function, user, amount))
+                }
+                StateMutability::Payable => {
+                    let value = self.rng.gen_range(1..=10_000u64);
+                    deposits[i] += value;
+                    (targets[i], U256::from(value), input(&mut self.rng, function, user, U256::from(value)))
+                }
+                _ if has_amount(function) => {
+                    if deposits[i] == 0 { continue }
+                    let amount = self.rng.gen_range(1..=deposits[i]);
+                    deposits[i] -= amount;
+                    (targets[i], U256::ZERO, input(&mut self.rng, function, user, U256::from(amount)))
+                }
+                _ => (targets[i], U256::ZERO, input(&mut self.rng, function, user, U256::ZERO)),
+            };
+            script.push(call);
+        }
+        script
+    }
+}
+
+// address arguments are the user, integer arguments are the amount
+fn input(rng: &mut StdRng, function: &Function, user: B160, amount: U256) -> Bytes {
+    let tokens = function.inputs.iter().map(|param| match param.kind {
+        ParamType::Address => Token::Address(user.into()),
+        ParamType::Uint(_) => Token::Uint(amount.into()),
+        ref kind => random_token(rng, kind, &[user], 2),
+    }).collect::<Vec<_>>();
+    function.encode_input(&tokens).unwrap().into()
+}
+
+fn has_amount(function: &Function) -> bool {
+    function.inputs.iter().any(|param| matches!(param.kind, ParamType::Uint(_)))
+}
+
+// fraction of benign calls into targets rejected by the defender, without any attacker
+pub fn false_positive_rate<D: Defender>(init_codes: &[Bytes], abis: &[Abi], defender: D, users: usize, calls: usize, seed: u64) -> f64 {
+    let mut env = Environment::<AttackerFixed, D>::with_seed(0, seed);
+    env.load_contracts(init_codes.to_vec());
+    let targets = env.get_contracts().iter().map(|x| x.0).collect::<Vec<_>>();
+    let mut workload = BenignWorkload::new(abis.to_vec(), seed);
+    for _ in 0..users {
+        let user = env.create_user_account();
+        env.load_user(workload.script(user, &targets, calls));
+    }
+    env.set_rounds(calls, 0);
+    env.load_defender(defender);
+    env.play().unwrap().false_positive_rate()
+}
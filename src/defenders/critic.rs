@@ -0,0 +1,60 @@
+use std::sync::{Arc, Mutex};
+use revm::interpreter::*;
+use revm::primitives::*;
+use tch::Tensor;
+use crate::environment::interfaces::Defender;
+use crate::neural::*;
+
+// rejects calls whose danger score is above a threshold
+// the danger score is the value of the game history up to and including the call, i.e. the expected attacker payoff
+pub struct DefenderCritic {
+    model: Arc<Model>,
+    critic: Arc<ValueHead>,
+    // in units of the value scale of training
+    pub threshold: f64,
+    // upper limit of bytecode tokens per target in the prefix
+    pub code_len: usize,
+    encoder: Option<TraceEncoder>,
+    histories: Arc<Mutex<Vec<Vec<i64>>>>,
+}
+
+impl DefenderCritic {
+    // the shared list gets the history of every finished game, for training the critic
+    // a history holds the code prefix and the checks of the calls that were not reverted, in the order they were made
+    pub fn new(model: Arc<Model>, critic: Arc<ValueHead>, threshold: f64) -> (Self, Arc<Mutex<Vec<Vec<i64>>>>) {
+        let histories = Arc::new(Mutex::new(Vec::new()));
+        (Self { model, critic, threshold, code_len: 256, encoder: None, histories: histories.clone() }, histories)
+    }
+    // value of the last prefix of tokens, only the last block_size tokens are seen
+    pub fn danger(&self, tokens: &[i64]) -> f64 {
+        let tokens = &tokens[tokens.len().saturating_sub(self.model.config.block_size as usize)..];
+        tch::no_grad(|| {
+            let idx = Tensor::from_slice(tokens).reshape([1, tokens.len() as i64]).to_device(self.model.device());
+            self.critic.forward(&self.model.hidden(&idx)).get(0).get(tokens.len() as i64 - 1).double_value(&[])
+        })
+    }
+}
+
+impl Defender for DefenderCritic {
+    // tokens of the game history as seen from a call, the calls it made are appended when they finish
+    type State = Vec<i64>;
+    fn init(&mut self, contracts: &[(B160, Bytes)]) -> Self::State {
+        // the defender does not know the attacker, its calls look like those of anyone else
+        self.encoder = Some(TraceEncoder::new(contracts, B160::zero()));
+        code_prefix(contracts, self.code_len)
+    }
+    fn check(&self, state: &Self::State, inputs: &CallInputs) -> (Self::State, bool) {
+        let Some(encoder) = self.encoder.as_ref() else { return (state.clone(), true) };
+        let mut history = state.clone();
+        history.extend(encoder.encode_event(&TraceEvent::check(inputs)));
+        let ok = self.danger(&history) <= self.threshold;
+        (history, ok)
+    }
+    fn call_end(&self, caller: &mut Self::State, state: Self::State, ok: bool) {
+        // reverted calls, rejected ones among them, leave no trace
+        if ok { *caller = state; }
+    }
+    fn finish(&mut self, state: Self::State) {
+        self.histories.lock().unwrap().push(state);
+    }
+}
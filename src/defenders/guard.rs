@@ -0,0 +1,72 @@
+use crate::environment::interfaces::Defender;
+use rand::Rng;
+use revm::interpreter::*;
+use revm::primitives::*;
+
+// a parameterised guard in front of every target
+#[derive(Clone, Debug, PartialEq)]
+pub struct DefenderGuard {
+    // upper limit of nested calls into targets, 1 forbids any reentrancy
+    pub max_depth: usize,
+    // upper limit of value sent with a call into a target
+    pub max_value: U256,
+    // function selectors that are always rejected
+    pub blocked: Vec<[u8; 4]>,
+}
+
+impl DefenderGuard {
+    // a guard that accepts every call
+    pub fn permissive() -> Self {
+        Self { max_depth: usize::MAX, max_value: U256::MAX, blocked: Vec::new() }
+    }
+    // sample a guard, selectors are the functions of the targets that could be blocked
+    pub fn random(rng: &mut impl Rng, selectors: &[[u8; 4]]) -> Self {
+        let max_depth = [1, 2, 4, usize::MAX][rng.gen_range(0..4)];
+        let max_value = match rng.gen_range(0..3) {
+            0 => U256::MAX,
+            1 => U256::from(rng.gen_range(0..=u32::MAX as u64)),
+            _ => U256::from(rng.gen_range(0..=u64::MAX / 4)),
+        };
+        let blocked = selectors.iter().filter(|_| rng.gen_bool(0.25)).copied().collect();
+        Self { max_depth, max_value, blocked }
+    }
+    // save as text, `depth <n>`, `value <x>` and one `block <selector>` line per blocked selector
+    pub fn save(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let mut s = format!("depth {}\nvalue {:#x}\n", self.max_depth, self.max_value);
+        for selector in self.blocked.iter() {
+            s += &format!("block 0x{}\n", hex::encode(selector));
+        }
+        std::fs::write(path, s)
+    }
+    // load a guard written by save
+    pub fn load(path: &std::path::Path) -> std::io::Result<Self> {
+        let bad = |line: usize| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("bad guard line {}", line + 1));
+        let mut guard = Self::permissive();
+        for (nr, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+            match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [] => continue,
+                ["depth", depth] => guard.max_depth = depth.parse().map_err(|_| bad(nr))?,
+                ["value", value] => guard.max_value = U256::from_str_radix(value.trim_start_matches("0x"), 16).map_err(|_| bad(nr))?,
+                ["block", selector] => {
+                    let selector = hex::decode(selector.trim_start_matches("0x")).map_err(|_| bad(nr))?;
+                    guard.blocked.push(selector.try_into().map_err(|_| bad(nr))?);
+                }
+                _ => return Err(bad(nr)),
+            }
+        }
+        Ok(guard)
+    }
+}
+
+impl Defender for DefenderGuard {
+    // number of calls into targets on the call stack
+    type State = usize;
+    fn check(&self, state: &Self::State, inputs: &CallInputs) -> (Self::State, bool) {
+        let depth = state + 1;
+        let blocked = inputs.input.len() >= 4 && self.blocked.iter().any(|s| s[..] == inputs.input[..4]);
+        (depth, depth <= self.max_depth && inputs.transfer.value <= self.max_value && !blocked)
+    }
+    fn init(&mut self, _contracts: &[(B160, Bytes)]) -> Self::State {
+        0
+    }
+}
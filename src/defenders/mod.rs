@@ -1,6 +1,14 @@
+mod benign;
 mod codegen;
+#[cfg(feature = "neural")]
+mod critic;
+mod guard;
 mod permissive;
 mod denial;
+pub use benign::*;
 pub use codegen::*;
+#[cfg(feature = "neural")]
+pub use critic::*;
+pub use guard::*;
 pub use permissive::*;
 pub use denial::*;
\ No newline at end of file
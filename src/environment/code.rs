@@ -0,0 +1,45 @@
+use revm::primitives::*;
+
+// runtime behaviour of the attacker contract, after the Attacker made its calls
+#[derive(Clone, Debug)]
+pub enum AttackerCode {
+    // succeed with empty return data
+    Stop,
+    // revert every call, which also rejects plain transfers
+    Revert,
+    // return fixed data, e.g. the selector expected by a token receiver hook
+    Return(Bytes),
+    // loop until out of gas
+    ConsumeGas,
+    // custom runtime bytecode
+    Runtime(Bytes),
+}
+
+impl AttackerCode {
+    pub fn runtime(&self) -> Bytes {
+        match self {
+            // STOP
+            AttackerCode::Stop => Bytes::from_static(&[0x00]),
+            // PUSH1 0 DUP1 REVERT
+            AttackerCode::Revert => Bytes::from_static(&[0x60, 0x00, 0x80, 0xfd]),
+            AttackerCode::Return(data) => deployer(data),
+            // JUMPDEST PUSH1 0 JUMP
+            AttackerCode::ConsumeGas => Bytes::from_static(&[0x5b, 0x60, 0x00, 0x56]),
+            AttackerCode::Runtime(code) => code.clone(),
+        }
+    }
+    // init code that deploys the runtime bytecode, accepting value
+    pub fn init_code(&self) -> Bytes {
+        deployer(&self.runtime())
+    }
+}
+
+// code that returns data as its output, used both as constructor and as `Return` runtime
+fn deployer(data: &[u8]) -> Bytes {
+    assert!(data.len() <= u16::MAX as usize, "data too long");
+    let [hi, lo] = (data.len() as u16).to_be_bytes();
+    // PUSH2 len DUP1 PUSH1 12 PUSH1 0 CODECOPY PUSH1 0 RETURN
+    let mut code = vec![0x61, hi, lo, 0x80, 0x60, 0x0c, 0x60, 0x00, 0x39, 0x60, 0x00, 0xf3];
+    code.extend_from_slice(data);
+    code.into()
+}
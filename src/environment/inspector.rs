@@ -1,20 +1,35 @@
 use revm::interpreter::*;
 use revm::primitives::*;
 use revm::{create_evm_impl, Database, EVMData, Inspector};
+use std::sync::{Arc, Mutex};
 use super::interfaces::*;
+use super::{AttackerKind, Coverage};
 
 pub struct GameInspector<DP: Defender, AP: Attacker, const TRACE: bool = false> {
     // the upper limit of attacker actions
     pub limit: usize,
-    // set of targets, attacker account
-    pub accounts: (HashSet<B160>, B160),
+    // set of targets, attacker accounts
+    pub accounts: (HashSet<B160>, Vec<B160>),
+    // benign user accounts
+    pub users: HashSet<B160>,
     // pure strategy of a defender
     pub defender: DP,
     pub defstate: Vec<DP::State>,
-    // pure strategy of an attacker
-    pub attacker: AP,
-    pub attstate: AP::State,
-    pub is_malicious: bool,
+    // pure strategies of attackers, one for each attacker account
+    pub attackers: Vec<AP>,
+    pub attstates: Vec<AP::State>,
+    pub is_malicious: Vec<bool>,
+    // the upper limit of calls an attacker makes when the game enters it, reentries are not limited
+    pub moves: usize,
+    // whether the last entry into an attacker made a call
+    pub moved: bool,
+    // calls into targets made in transactions of benign users, and how many the defender rejected
+    pub benign_calls: usize,
+    pub benign_rejected: usize,
+    // sink of program counters reached in targets
+    pub coverage: Option<Arc<Mutex<Coverage>>>,
+    // kind of each attacker account, in the order of accounts
+    pub kinds: Vec<AttackerKind>,
 }
 
 impl<DB: Database, DP: Defender, AP: Attacker, const TRACE: bool> 
@@ -23,7 +38,7 @@ impl<DB: Database, DP: Defender, AP: Attacker, const TRACE: bool>
     fn initialize_interp(&mut self, interp: &mut Interpreter, _data: &mut EVMData<'_,DB> ,) -> InstructionResult {
         if !TRACE { return InstructionResult::Continue }
         println!("===");
-        if interp.contract.address == self.accounts.1 {
+        if self.accounts.1.contains(&interp.contract.address) {
             println!("contract attacker");
         }
         else if self.accounts.0.contains(&interp.contract.address) {
@@ -34,10 +49,17 @@ impl<DB: Database, DP: Defender, AP: Attacker, const TRACE: bool>
         }
         InstructionResult::Continue
     }
-    fn step(&mut self, interp: &mut Interpreter, _data: &mut EVMData<'_,DB> ) -> InstructionResult {
-        if interp.contract.address == self.accounts.1 {
-            InstructionResult::Stop
+    fn step(&mut self, interp: &mut Interpreter, data: &mut EVMData<'_,DB> ) -> InstructionResult {
+        if let Some(i) = self.accounts.1.iter().position(|x| *x == interp.contract.address) {
+            // the transaction that starts the game never runs attacker code
+            let entry = interp.contract.caller == data.env.tx.caller;
+            if self.kinds[i] == AttackerKind::Code && !entry { InstructionResult::Continue } else { InstructionResult::Stop }
         } else {
+            if let Some(coverage) = &self.coverage {
+                if self.accounts.0.contains(&interp.contract.address) {
+                    coverage.lock().unwrap().insert((interp.contract.address, interp.program_counter()));
+                }
+            }
             InstructionResult::Continue
         }
     }
@@ -68,19 +90,34 @@ impl<DB: Database, DP: Defender, AP: Attacker, const TRACE: bool>
         data: &mut EVMData<'_, DB>,
         inputs: &mut CallInputs,
     ) -> (InstructionResult, Gas, Bytes) {
+        let attacker = self.accounts.1.iter().position(|x| *x == inputs.contract);
         if self.accounts.0.contains(&inputs.contract) {
             // check before function calls
             let (state, ok) = self.defender.check(self.defstate.last().unwrap_or_else(|| panic!()), inputs);
             self.defstate.push(state);
+            if self.users.contains(&data.env.tx.caller) {
+                self.benign_calls += 1;
+                self.benign_rejected += !ok as usize;
+            }
             let ok = if ok { InstructionResult::Continue } else { InstructionResult::Revert };
             (ok, Gas::new(0), Bytes::default())
         }
-        else if self.accounts.1 == inputs.contract {
+        else if self.users.contains(&inputs.contract) || attacker.map_or(false, |i| self.kinds[i] == AttackerKind::Eoa) {
+            // plain transfer to an externally owned account
+            (InstructionResult::Continue, Gas::new(0), Bytes::default())
+        }
+        else if let Some(i) = attacker {
+            // entries from the transaction that starts a move are limited to a number of calls
+            let entry = inputs.context.caller == data.env.tx.caller;
+            let mut moves = if entry { self.moves } else { usize::MAX };
+            if entry { self.moved = false; }
             // iterate over attacker calls, let attacker process input
-            while self.limit > 0 {
-                let Some((addr, value, input)) = self.attacker.make_mal_call(&mut self.attstate)
+            while self.limit > 0 && moves > 0 {
+                let Some((addr, value, input)) = self.attackers[i].make_mal_call(&mut self.attstates[i])
                     else { break };
                 self.limit -= 1;
+                moves -= 1;
+                if entry { self.moved = true; }
                 // boilerplate call
                 let (ret, gas, out) = create_evm_impl::<DB, true>(data, self)
                     .call(&mut CallInputs {
@@ -92,10 +129,10 @@ impl<DB: Database, DP: Defender, AP: Attacker, const TRACE: bool>
                             apparent_value: value, scheme: CallScheme::Call, 
                         }, 
                     });
-                self.attacker.take_return(&mut self.attstate, ret, gas, out);
+                self.attackers[i].take_return(&mut self.attstates[i], ret, gas, out);
             }
             // decide whether a call should fail
-            let ok = self.attacker.check(&mut self.attstate);
+            let ok = self.attackers[i].check(&mut self.attstates[i]);
             let ok = if ok { InstructionResult::Continue } else {InstructionResult::Revert };
             (ok, Gas::new(0), Bytes::default())
         }
@@ -113,8 +150,31 @@ impl<DB: Database, DP: Defender, AP: Attacker, const TRACE: bool>
         out: Bytes,
     ) -> (InstructionResult, Gas, Bytes) {
         if self.accounts.0.contains(&inputs.contract) {
-            self.defstate.pop();
+            let state = self.defstate.pop().unwrap();
+            let ok = matches!(ret, InstructionResult::Stop | InstructionResult::Return | InstructionResult::SelfDestruct);
+            self.defender.call_end(self.defstate.last_mut().unwrap(), state, ok);
         }
         (ret, remaining_gas, out)
     }
+}
+
+// lets a game inspector run several transactions, keeping attacker and defender states
+impl<'a, DB: Database, DP: Defender, AP: Attacker, const TRACE: bool>
+    Inspector<DB> for &'a mut GameInspector<DP, AP, TRACE>
+{
+    fn initialize_interp(&mut self, interp: &mut Interpreter, data: &mut EVMData<'_,DB> ,) -> InstructionResult {
+        Inspector::<DB>::initialize_interp(&mut **self, interp, data)
+    }
+    fn step(&mut self, interp: &mut Interpreter, data: &mut EVMData<'_,DB> ) -> InstructionResult {
+        Inspector::<DB>::step(&mut **self, interp, data)
+    }
+    fn step_end(&mut self, interp: &mut Interpreter, data: &mut EVMData<'_,DB> , eval: InstructionResult,) -> InstructionResult {
+        Inspector::<DB>::step_end(&mut **self, interp, data, eval)
+    }
+    fn call(&mut self, data: &mut EVMData<'_, DB>, inputs: &mut CallInputs) -> (InstructionResult, Gas, Bytes) {
+        Inspector::<DB>::call(&mut **self, data, inputs)
+    }
+    fn call_end(&mut self, data: &mut EVMData<'_, DB>, inputs: &CallInputs, remaining_gas: Gas, ret: InstructionResult, out: Bytes) -> (InstructionResult, Gas, Bytes) {
+        Inspector::<DB>::call_end(&mut **self, data, inputs, remaining_gas, ret, out)
+    }
 }
\ No newline at end of file
@@ -7,6 +7,11 @@ pub trait Defender {
     fn init(&mut self, contracts: &[(B160, Bytes)]) -> Self::State;
     // check current contract
     fn check(&self, state: &Self::State, inputs: &CallInputs) -> (Self::State, bool);
+    // hand the state of a finished call back to the state of its caller, ok if the call did not revert
+    // by default the state of the call is dropped
+    fn call_end(&self, _caller: &mut Self::State, _state: Self::State, _ok: bool) {}
+    // take the state of a finished game, with the calls of every transaction handed back
+    fn finish(&mut self, _state: Self::State) {}
 }
 
 pub trait Attacker {
@@ -20,3 +25,100 @@ pub trait Attacker {
     // check made by attacker
     fn check(&self, state: &mut Self::State) -> bool;
 }
+
+// an attacker of any type, so that different attackers can play in the same kind of environment
+pub struct BoxAttacker(Box<dyn ErasedAttacker + Send>);
+
+impl BoxAttacker {
+    pub fn new<A: Attacker + Send + 'static>(attacker: A) -> Self where A::State: 'static {
+        BoxAttacker(Box::new(attacker))
+    }
+}
+
+// object safe form of Attacker, kept private so that it never shadows Attacker methods
+trait ErasedAttacker {
+    fn init(&mut self, contracts: &[(B160, Bytes)]) -> (bool, Box<dyn std::any::Any>);
+    fn make_mal_call(&self, state: &mut Box<dyn std::any::Any>) -> Option<(B160, U256, Bytes)>;
+    fn take_return(&self, state: &mut Box<dyn std::any::Any>, ret: InstructionResult, gas: Gas, out: Bytes);
+    fn check(&self, state: &mut Box<dyn std::any::Any>) -> bool;
+}
+
+impl<A: Attacker> ErasedAttacker for A where A::State: 'static {
+    fn init(&mut self, contracts: &[(B160, Bytes)]) -> (bool, Box<dyn std::any::Any>) {
+        let (is_malicious, state) = Attacker::init(self, contracts);
+        (is_malicious, Box::new(state))
+    }
+    fn make_mal_call(&self, state: &mut Box<dyn std::any::Any>) -> Option<(B160, U256, Bytes)> {
+        Attacker::make_mal_call(self, state.downcast_mut().unwrap())
+    }
+    fn take_return(&self, state: &mut Box<dyn std::any::Any>, ret: InstructionResult, gas: Gas, out: Bytes) {
+        Attacker::take_return(self, state.downcast_mut().unwrap(), ret, gas, out)
+    }
+    fn check(&self, state: &mut Box<dyn std::any::Any>) -> bool {
+        Attacker::check(self, state.downcast_mut().unwrap())
+    }
+}
+
+impl Attacker for BoxAttacker {
+    type State = Box<dyn std::any::Any>;
+    fn init(&mut self, contracts: &[(B160, Bytes)]) -> (bool, Self::State) {
+        self.0.init(contracts)
+    }
+    fn make_mal_call(&self, state: &mut Self::State) -> Option<(B160, U256, Bytes)> {
+        self.0.make_mal_call(state)
+    }
+    fn take_return(&self, state: &mut Self::State, ret: InstructionResult, gas: Gas, out: Bytes) {
+        self.0.take_return(state, ret, gas, out)
+    }
+    fn check(&self, state: &mut Self::State) -> bool {
+        self.0.check(state)
+    }
+}
+
+// a defender of any type
+pub struct BoxDefender(Box<dyn ErasedDefender + Send>);
+
+impl BoxDefender {
+    pub fn new<D: Defender + Send + 'static>(defender: D) -> Self where D::State: 'static {
+        BoxDefender(Box::new(defender))
+    }
+}
+
+trait ErasedDefender {
+    fn init(&mut self, contracts: &[(B160, Bytes)]) -> Box<dyn std::any::Any>;
+    fn check(&self, state: &Box<dyn std::any::Any>, inputs: &CallInputs) -> (Box<dyn std::any::Any>, bool);
+    fn call_end(&self, caller: &mut Box<dyn std::any::Any>, state: Box<dyn std::any::Any>, ok: bool);
+    fn finish(&mut self, state: Box<dyn std::any::Any>);
+}
+
+impl<D: Defender> ErasedDefender for D where D::State: 'static {
+    fn init(&mut self, contracts: &[(B160, Bytes)]) -> Box<dyn std::any::Any> {
+        Box::new(Defender::init(self, contracts))
+    }
+    fn check(&self, state: &Box<dyn std::any::Any>, inputs: &CallInputs) -> (Box<dyn std::any::Any>, bool) {
+        let (state, ok) = Defender::check(self, state.downcast_ref().unwrap(), inputs);
+        (Box::new(state), ok)
+    }
+    fn call_end(&self, caller: &mut Box<dyn std::any::Any>, state: Box<dyn std::any::Any>, ok: bool) {
+        Defender::call_end(self, caller.downcast_mut().unwrap(), *state.downcast().unwrap(), ok)
+    }
+    fn finish(&mut self, state: Box<dyn std::any::Any>) {
+        Defender::finish(self, *state.downcast().unwrap())
+    }
+}
+
+impl Defender for BoxDefender {
+    type State = Box<dyn std::any::Any>;
+    fn init(&mut self, contracts: &[(B160, Bytes)]) -> Self::State {
+        self.0.init(contracts)
+    }
+    fn check(&self, state: &Self::State, inputs: &CallInputs) -> (Self::State, bool) {
+        self.0.check(state, inputs)
+    }
+    fn call_end(&self, caller: &mut Self::State, state: Self::State, ok: bool) {
+        self.0.call_end(caller, state, ok)
+    }
+    fn finish(&mut self, state: Self::State) {
+        self.0.finish(state)
+    }
+}
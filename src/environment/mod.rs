@@ -1,17 +1,67 @@
 pub mod interfaces;
 pub mod inspector;
+mod code;
+pub use code::*;
 use revm::db::*;
 use revm::primitives::*;
+use revm::interpreter::{Gas, InstructionResult};
 use self::interfaces::Attacker;
 use self::interfaces::Defender;
 use once_cell::sync::Lazy;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::sync::{Arc, Mutex};
+
+// program counters reached in target contracts
+pub type Coverage = HashSet<(B160, usize)>;
 
 pub struct Environment<A: Attacker, D: Defender, const TRACE: bool=false> {
     db: CacheDB<EmptyDB>,
     limit: usize,
     contracts: Vec<(B160, Bytes)>,
-    attacker: (Option<B160>, Option<A>),
+    // attacker accounts, and the attackers controlling them in the same order
+    attacker: (Vec<B160>, Vec<A>),
+    // kind of each attacker account, in the same order
+    kinds: Vec<AttackerKind>,
     defender: Option<D>,
+    // benign user accounts, and the calls they make in the same order, one call per round
+    users: (Vec<B160>, Vec<Vec<(B160, U256, Bytes)>>),
+    // number of rounds, and the upper limit of calls an attacker makes in each round
+    rounds: (usize, usize),
+    // source of account addresses, seeded games are reproducible
+    rng: StdRng,
+    coverage: Option<Arc<Mutex<Coverage>>>,
+}
+
+// how the game treats an attacker account
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttackerKind {
+    // a contract whose bytecode is stopped, the attacker acts when it is entered
+    Contract,
+    // a contract whose bytecode runs after the attacker made its calls
+    Code,
+    // an externally owned account, it sends its calls as transactions and is never called back
+    Eoa,
+}
+
+// outcome of a game
+#[derive(Clone, Debug)]
+pub struct GameReport {
+    // balance of each attacker account before and after the game
+    pub balances: Vec<(U256, U256)>,
+    // calls into targets made in transactions of benign users, and how many the defender rejected
+    pub benign_calls: usize,
+    pub benign_rejected: usize,
+}
+
+impl GameReport {
+    // total balance change of all attackers
+    pub fn payoff(&self) -> f64 {
+        self.balances.iter().map(|(x, y)| crate::utils::payoff(*x, *y)).sum()
+    }
+    // fraction of benign calls rejected by the defender
+    pub fn false_positive_rate(&self) -> f64 {
+        if self.benign_calls == 0 { 0.0 } else { self.benign_rejected as f64 / self.benign_calls as f64 }
+    }
 }
 
 static INIT_CODE: Lazy<Bytes> = Lazy::new(|| {
@@ -21,15 +71,73 @@ static INIT_CODE: Lazy<Bytes> = Lazy::new(|| {
 
 impl<A: Attacker, D: Defender, const TRACE: bool> Environment<A, D, TRACE> {
     pub fn new(limit: usize) -> Self {
-        Self { db: CacheDB::new(EmptyDB::default()), attacker: (None, None), defender: None, contracts: Vec::new(), limit }
+        Self::with_seed(limit, rand::random())
+    }
+    pub fn with_seed(limit: usize, seed: u64) -> Self {
+        Self {
+            db: CacheDB::new(EmptyDB::default()), attacker: (Vec::new(), Vec::new()), kinds: Vec::new(), defender: None,
+            users: (Vec::new(), Vec::new()), rounds: (1, usize::MAX), contracts: Vec::new(), limit,
+            rng: StdRng::seed_from_u64(seed), coverage: None,
+        }
+    }
+    // play several rounds, in each round every attacker makes at most moves calls
+    // when entered by the game, then every benign user makes its next call
+    pub fn set_rounds(&mut self, rounds: usize, moves: usize) {
+        self.rounds = (rounds, moves);
     }
     pub fn get_contracts(&self) -> &[(B160, Bytes)] {
         &self.contracts
     }
     pub fn create_attacker_account(&mut self) -> B160 {
+        self.deploy_attacker(Bytes::from(INIT_CODE.as_ref()), AttackerKind::Contract)
+    }
+    // create an attacker account whose code runs after each attacker entry, init code must accept value
+    pub fn create_attacker_account_with(&mut self, code: &AttackerCode) -> B160 {
+        self.create_attacker_account_from(code.init_code())
+    }
+    pub fn create_attacker_account_from(&mut self, init_code: Bytes) -> B160 {
+        self.deploy_attacker(init_code, AttackerKind::Code)
+    }
+    // create an attacker account without code
+    // it sends one top level transaction per call and is never called back, so it cannot reenter
+    pub fn create_attacker_eoa(&mut self) -> B160 {
+        let addr = B160::from(self.rng.gen::<u64>());
+        self.db.insert_account_info(
+            addr, 
+            AccountInfo {
+                balance: U256::from(u64::MAX), 
+                nonce: 0, 
+                code_hash: KECCAK_EMPTY, 
+                code: None 
+            }
+        );
+        self.attacker.0.push(addr);
+        self.kinds.push(AttackerKind::Eoa);
+        addr
+    }
+    // create a benign user account without code
+    pub fn create_user_account(&mut self) -> B160 {
+        let addr = B160::from(self.rng.gen::<u64>());
+        self.db.insert_account_info(
+            addr, 
+            AccountInfo {
+                balance: U256::from(u64::MAX), 
+                nonce: 0, 
+                code_hash: KECCAK_EMPTY, 
+                code: None 
+            }
+        );
+        self.users.0.push(addr);
+        addr
+    }
+    // load the calls of a benign user, the n-th loaded calls are made by the n-th user account
+    pub fn load_user(&mut self, calls: Vec<(B160, U256, Bytes)>) {
+        self.users.1.push(calls);
+    }
+    fn deploy_attacker(&mut self, init_code: Bytes, kind: AttackerKind) -> B160 {
         // create an attacker account
         let mut evm = revm::EVM::new();
-        let admin = B160::from(rand::random::<u64>() as u64);
+        let admin = B160::from(self.rng.gen::<u64>());
         self.db.insert_account_info(
             admin, 
             AccountInfo {
@@ -42,7 +150,7 @@ impl<A: Attacker, D: Defender, const TRACE: bool> Environment<A, D, TRACE> {
         evm.database(&mut self.db);
         evm.env.tx.caller = admin;
         evm.env.tx.transact_to = TransactTo::Create(CreateScheme::Create);
-        evm.env.tx.data = Bytes::from(INIT_CODE.as_ref());
+        evm.env.tx.data = init_code;
         evm.env.tx.value = U256::from(u64::MAX);
         let result = evm.transact_commit().unwrap();
         let addr = match result {
@@ -50,12 +158,17 @@ impl<A: Attacker, D: Defender, const TRACE: bool> Environment<A, D, TRACE> {
                 => address,
             result => panic!("contract creation failed: {result:?}"),
         };
-        self.attacker.0 = Some(addr);
+        self.attacker.0.push(addr);
+        self.kinds.push(kind);
         return addr;
     }
-    // load attacker
+    // collect program counters reached in target contracts into sink
+    pub fn track_coverage(&mut self, sink: Arc<Mutex<Coverage>>) {
+        self.coverage = Some(sink);
+    }
+    // load attacker, the n-th loaded attacker controls the n-th attacker account
     pub fn load_attacker(&mut self, attacker: A) {
-        self.attacker.1 = Some(attacker);
+        self.attacker.1.push(attacker);
     }
     // load defender
     pub fn load_defender(&mut self, defender: D) {
@@ -72,7 +185,7 @@ impl<A: Attacker, D: Defender, const TRACE: bool> Environment<A, D, TRACE> {
     // load contract bytecode
     pub fn load_contracts(&mut self, target_init_codes: Vec<Bytes>) {
         // create an administrator account
-        let admin = B160::from(rand::random::<u64>());
+        let admin = B160::from(self.rng.gen::<u64>());
         // add an administator account
         self.db.insert_account_info(
             admin, 
@@ -114,14 +227,31 @@ impl<A: Attacker, D: Defender, const TRACE: bool> Environment<A, D, TRACE> {
             }
         }
     }
-    // compute attacker initial value
-    pub fn attacker_balance(&mut self) -> U256 {
-        self.db.load_account(self.attacker.0.unwrap()).unwrap().info.balance.clone()
+    // compute initial value of every attacker
+    pub fn attacker_balances(&mut self) -> Vec<U256> {
+        let accounts = self.attacker.0.clone();
+        accounts.into_iter().map(|a| self.db.load_account(a).unwrap().info.balance.clone()).collect()
+    }
+    // compute the final value of the only attacker
+    pub fn compute(self) -> U256 {
+        // see if there is a halt / revert (should never happen)
+        self.try_compute().unwrap_or_else(|result| panic!("{result:?}"))
+    }
+    // compute attacker balance change, None if the game does not succeed
+    pub fn payoff(self) -> Option<f64> {
+        Some(self.play().ok()?.payoff())
+    }
+    // compute the final value of the only attacker, or the execution result if the game does not succeed
+    // games with several attackers are reported by play
+    pub fn try_compute(self) -> Result<U256, ExecutionResult> {
+        assert_eq!(self.attacker.0.len(), 1, "compute needs exactly one attacker account");
+        let report = self.play()?;
+        Ok(report.balances[0].1)
     }
-    // compute attacker final value
-    pub fn compute(mut self) -> U256 {
+    // play the game, or give the execution result of the first attacker transaction that does not succeed
+    pub fn play(mut self) -> Result<GameReport, ExecutionResult> {
         // create an administrator account
-        let admin = B160::from(rand::random::<u64>());
+        let admin = B160::from(self.rng.gen::<u64>());
         // add an administator account
         self.db.insert_account_info(
             admin, 
@@ -132,27 +262,98 @@ impl<A: Attacker, D: Defender, const TRACE: bool> Environment<A, D, TRACE> {
                 code: None 
             }
         );
-        // initialize attacker and defender with contracts
-        let (is_malicious, attstate) = self.attacker.1.as_mut().unwrap().init(&self.contracts);
+        assert_eq!(self.attacker.0.len(), self.attacker.1.len(), "every attacker account needs an attacker");
+        let before = self.attacker_balances();
+        // initialize attackers and defender with contracts
+        let (is_malicious, attstates): (Vec<bool>, Vec<A::State>) = self.attacker.1.iter_mut().map(|a| a.init(&self.contracts)).unzip();
         let defstate = vec![self.defender.as_mut().unwrap().init(&self.contracts)];
-        let inspector = inspector::GameInspector::<D, A, TRACE> {
+        let mut inspector = inspector::GameInspector::<D, A, TRACE> {
             limit: self.limit,
-            accounts: (HashSet::from_iter(self.contracts.iter().map(|x| x.0)), self.attacker.0.unwrap()),
-            attacker: self.attacker.1.unwrap(), 
-            defender: self.defender.unwrap(), 
-            attstate, defstate, is_malicious,
+            accounts: (HashSet::from_iter(self.contracts.iter().map(|x| x.0)), self.attacker.0.clone()),
+            users: HashSet::from_iter(self.users.0.iter().copied()),
+            attackers: std::mem::take(&mut self.attacker.1), 
+            defender: self.defender.take().unwrap(), 
+            attstates, defstate, is_malicious,
+            moves: self.rounds.1, moved: false,
+            benign_calls: 0, benign_rejected: 0,
+            coverage: self.coverage.clone(),
+            kinds: self.kinds.clone(),
         };
-        // call attacker to start the game, addr is attacker address
-        let mut evm = revm::EVM::new();
-        evm.database(&mut self.db);
-        evm.env.tx.caller = admin;
-        evm.env.tx.transact_to = TransactTo::Call(self.attacker.0.unwrap());
-        evm.env.tx.data = Bytes::default();
-        evm.env.tx.value = U256::from(0);
-        let execution_result = evm.inspect_commit(inspector).unwrap();
-        // see if there is a halt / revert (should never happen)
-        assert!(matches!(execution_result, ExecutionResult::Success{ .. }), "{execution_result:?}");
+        for round in 0..self.rounds.0 {
+            let mut active = false;
+            for i in 0..self.attacker.0.len() {
+                if inspector.limit == 0 { break }
+                if self.kinds[i] == AttackerKind::Eoa {
+                    active |= Self::move_eoa(&mut self.db, self.attacker.0[i], i, &mut inspector);
+                    continue;
+                }
+                // call attacker to start its move, addr is attacker address
+                let mut evm = revm::EVM::new();
+                evm.database(&mut self.db);
+                evm.env.tx.caller = admin;
+                evm.env.tx.transact_to = TransactTo::Call(self.attacker.0[i]);
+                evm.env.tx.data = Bytes::default();
+                evm.env.tx.value = U256::from(0);
+                let execution_result = evm.inspect_commit(&mut inspector).unwrap();
+                if !matches!(execution_result, ExecutionResult::Success{ .. }) {
+                    return Err(execution_result);
+                }
+                active |= inspector.moved;
+            }
+            // benign users make their calls between attacker moves, failures are part of the report
+            for (user, calls) in self.users.0.iter().zip(self.users.1.iter()) {
+                let Some((addr, value, input)) = calls.get(round).cloned() else { continue };
+                active = true;
+                let mut evm = revm::EVM::new();
+                evm.database(&mut self.db);
+                evm.env.tx.caller = *user;
+                evm.env.tx.transact_to = TransactTo::Call(addr);
+                evm.env.tx.data = input;
+                evm.env.tx.value = value;
+                evm.env.tx.gas_limit = 10_000_000;
+                let _ = evm.inspect_commit(&mut inspector);
+            }
+            if !active { break }
+        }
+        inspector.defender.finish(inspector.defstate.pop().unwrap());
         // give the final utility
-        self.db.load_account(self.attacker.0.unwrap()).unwrap().info.balance.clone()
+        let after = self.attacker_balances();
+        Ok(GameReport {
+            balances: before.into_iter().zip(after).collect(),
+            benign_calls: inspector.benign_calls,
+            benign_rejected: inspector.benign_rejected,
+        })
+    }
+    // the i-th attacker sends each call of its move as a top level transaction, returns whether it made a call
+    fn move_eoa(db: &mut CacheDB<EmptyDB>, attacker: B160, i: usize, inspector: &mut inspector::GameInspector<D, A, TRACE>) -> bool {
+        let mut moved = false;
+        for _ in 0..inspector.moves {
+            if inspector.limit == 0 { break }
+            let Some((addr, value, input)) = inspector.attackers[i].make_mal_call(&mut inspector.attstates[i])
+                else { break };
+            inspector.limit -= 1;
+            moved = true;
+            let mut evm = revm::EVM::new();
+            evm.database(&mut *db);
+            evm.env.tx.caller = attacker;
+            evm.env.tx.transact_to = TransactTo::Call(addr);
+            evm.env.tx.data = input;
+            evm.env.tx.value = value;
+            evm.env.tx.gas_limit = 10_000_000;
+            // a transaction that cannot be paid for is a failed call
+            let (ret, gas, out) = match evm.inspect_commit(&mut *inspector) {
+                Ok(ExecutionResult::Success { gas_used, output, .. }) =>
+                    (InstructionResult::Return, gas_used, output.into_data()),
+                Ok(ExecutionResult::Revert { gas_used, output }) =>
+                    (InstructionResult::Revert, gas_used, output),
+                Ok(ExecutionResult::Halt { gas_used, .. }) =>
+                    (InstructionResult::OutOfGas, gas_used, Bytes::default()),
+                Err(_) => (InstructionResult::OutOfFund, 0, Bytes::default()),
+            };
+            let mut remaining = Gas::new(10_000_000);
+            remaining.record_cost(gas);
+            inspector.attackers[i].take_return(&mut inspector.attstates[i], ret, remaining, out);
+        }
+        moved
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,8 @@
+mod tournament;
+pub use tournament::*;
+mod nash;
+pub use nash::*;
+mod psro;
+pub use psro::*;
+mod stackelberg;
+pub use stackelberg::*;
@@ -0,0 +1,133 @@
+use super::PayoffMatrix;
+
+// mixed strategies of a zero-sum game, the attacker is the row player and maximises
+#[derive(Clone, Debug)]
+pub struct Equilibrium {
+    pub attacker: Vec<f64>,
+    pub defender: Vec<f64>,
+    // expected payoff of the attacker under both mixtures
+    pub value: f64,
+}
+
+impl PayoffMatrix {
+    // exact equilibrium of the game of mean payoffs
+    pub fn equilibrium(&self) -> Equilibrium {
+        solve_lp(&self.means())
+    }
+}
+
+// expected payoff of the attacker
+pub fn expected(matrix: &[Vec<f64>], attacker: &[f64], defender: &[f64]) -> f64 {
+    matrix.iter().zip(attacker).map(|(row, x)| x * row.iter().zip(defender).map(|(a, y)| a * y).sum::<f64>()).sum()
+}
+
+// what either side gains by deviating to a best response, zero at an equilibrium
+pub fn exploitability(matrix: &[Vec<f64>], eq: &Equilibrium) -> f64 {
+    let best_attack = matrix.iter()
+        .map(|row| row.iter().zip(&eq.defender).map(|(a, y)| a * y).sum::<f64>())
+        .fold(f64::NEG_INFINITY, f64::max);
+    let best_defence = (0..eq.defender.len())
+        .map(|j| matrix.iter().zip(&eq.attacker).map(|(row, x)| row[j] * x).sum::<f64>())
+        .fold(f64::INFINITY, f64::min);
+    best_attack - best_defence
+}
+
+// linear program of the defender, solved with the simplex method
+// after shifting payoffs to be positive, the defender maximises sum y subject to A y <= 1, y >= 0
+// the attacker mixture is read from the dual values of the constraints
+pub fn solve_lp(matrix: &[Vec<f64>]) -> Equilibrium {
+    let (m, n) = (matrix.len(), matrix[0].len());
+    let shift = 1.0 - matrix.iter().flatten().fold(f64::INFINITY, |a, b| a.min(*b));
+    // tableau rows are [A | I | 1], the last row is the objective [-1 | 0 | 0]
+    let width = n + m + 1;
+    let mut t = vec![vec![0.0; width]; m + 1];
+    for i in 0..m {
+        for j in 0..n { t[i][j] = matrix[i][j] + shift; }
+        t[i][n + i] = 1.0;
+        t[i][width - 1] = 1.0;
+    }
+    for j in 0..n { t[m][j] = -1.0; }
+    let mut basis = (n..n + m).collect::<Vec<_>>();
+    const EPS: f64 = 1e-12;
+    // bland's rule, the lowest entering and leaving variables, so that the method cannot cycle
+    while let Some(col) = (0..n + m).find(|&j| t[m][j] < -EPS) {
+        let row = (0..m)
+            .filter(|&i| t[i][col] > EPS)
+            .min_by(|&a, &b| (t[a][width - 1] / t[a][col]).total_cmp(&(t[b][width - 1] / t[b][col])).then(basis[a].cmp(&basis[b])))
+            .expect("the game lp is bounded");
+        let p = t[row][col];
+        t[row].iter_mut().for_each(|x| *x /= p);
+        for i in 0..=m {
+            if i == row || t[i][col] == 0.0 { continue }
+            let f = t[i][col];
+            for j in 0..width { t[i][j] -= f * t[row][j]; }
+        }
+        basis[row] = col;
+    }
+    let total = t[m][width - 1];
+    let mut defender = vec![0.0; n];
+    for (i, &b) in basis.iter().enumerate() {
+        if b < n { defender[b] = t[i][width - 1] / total; }
+    }
+    let attacker = (0..m).map(|i| t[m][n + i] / total).collect();
+    Equilibrium { attacker, defender, value: 1.0 / total - shift }
+}
+
+// both sides repeatedly best respond to the empirical mixture of the other
+pub fn fictitious_play(matrix: &[Vec<f64>], iterations: usize) -> Equilibrium {
+    let (m, n) = (matrix.len(), matrix[0].len());
+    let (mut attacker, mut defender) = (vec![0.0; m], vec![0.0; n]);
+    // cumulative payoff of each pure strategy against the other side's history
+    let (mut row_payoff, mut col_payoff) = (vec![0.0; m], vec![0.0; n]);
+    let (mut i, mut j) = (0, 0);
+    for _ in 0..iterations.max(1) {
+        attacker[i] += 1.0;
+        defender[j] += 1.0;
+        for (r, row) in row_payoff.iter_mut().zip(matrix) { *r += row[j]; }
+        for (c, col) in col_payoff.iter_mut().enumerate() { *col += matrix[i][c]; }
+        i = argmax(&row_payoff);
+        j = argmax(&col_payoff.iter().map(|x| -x).collect::<Vec<_>>());
+    }
+    finish(matrix, attacker, defender)
+}
+
+// both sides play in proportion to positive regret, the average strategies converge
+pub fn regret_matching(matrix: &[Vec<f64>], iterations: usize) -> Equilibrium {
+    let (m, n) = (matrix.len(), matrix[0].len());
+    let (mut row_regret, mut col_regret) = (vec![0.0; m], vec![0.0; n]);
+    let (mut attacker, mut defender) = (vec![0.0; m], vec![0.0; n]);
+    for _ in 0..iterations.max(1) {
+        let x = matching(&row_regret);
+        let y = matching(&col_regret);
+        let value = expected(matrix, &x, &y);
+        for (i, r) in row_regret.iter_mut().enumerate() {
+            *r += matrix[i].iter().zip(&y).map(|(a, y)| a * y).sum::<f64>() - value;
+        }
+        for (j, r) in col_regret.iter_mut().enumerate() {
+            *r += value - matrix.iter().zip(&x).map(|(row, x)| row[j] * x).sum::<f64>();
+        }
+        attacker.iter_mut().zip(&x).for_each(|(a, x)| *a += x);
+        defender.iter_mut().zip(&y).for_each(|(a, y)| *a += y);
+    }
+    finish(matrix, attacker, defender)
+}
+
+// a mixture proportional to positive regrets, uniform if there are none
+fn matching(regret: &[f64]) -> Vec<f64> {
+    let sum = regret.iter().map(|r| r.max(0.0)).sum::<f64>();
+    if sum <= 0.0 { return vec![1.0 / regret.len() as f64; regret.len()] }
+    regret.iter().map(|r| r.max(0.0) / sum).collect()
+}
+
+fn argmax(xs: &[f64]) -> usize {
+    (0..xs.len()).max_by(|&a, &b| xs[a].total_cmp(&xs[b]).then(b.cmp(&a))).unwrap()
+}
+
+// normalise counts into mixtures
+fn finish(matrix: &[Vec<f64>], attacker: Vec<f64>, defender: Vec<f64>) -> Equilibrium {
+    let (a, d) = (attacker.iter().sum::<f64>(), defender.iter().sum::<f64>());
+    let attacker = attacker.into_iter().map(|x| x / a).collect::<Vec<_>>();
+    let defender = defender.into_iter().map(|x| x / d).collect::<Vec<_>>();
+    let value = expected(matrix, &attacker, &defender);
+    Equilibrium { attacker, defender, value }
+}
@@ -0,0 +1,211 @@
+use std::path::PathBuf;
+use rand::prelude::*;
+use crate::attackers::{AttackerFixed, AttackerFuzz, AttackerNeural, CallGroup, FuzzConfig, FuzzCorpus};
+use crate::defenders::DefenderGuard;
+use crate::environment::interfaces::{BoxAttacker, BoxDefender};
+use crate::search::{Gp, GpConfig};
+use super::{solve_lp, Equilibrium, Setup, Tournament};
+
+pub struct PsroConfig {
+    // generations of the genetic programming oracle of the attacker
+    pub generations: usize,
+    pub gp: GpConfig,
+    // guards sampled by the oracle of the defender
+    pub candidates: usize,
+    // loss of the defender per unit of false positive rate, measured with benign users
+    pub false_positive_cost: f64,
+    pub users: usize,
+    pub calls: usize,
+    // a best response joins its population only if it beats the meta-game value by more than this
+    pub tolerance: f64,
+    // directory for a checkpoint of every population member after each iteration
+    pub checkpoint: Option<PathBuf>,
+    pub seed: u64,
+}
+
+impl Default for PsroConfig {
+    fn default() -> Self {
+        Self {
+            generations: 8, gp: GpConfig::default(), candidates: 32,
+            false_positive_cost: 1e18, users: 4, calls: 8,
+            tolerance: 0.0, checkpoint: None, seed: 0,
+        }
+    }
+}
+
+// an attacker strategy found by some search
+#[derive(Clone)]
+pub enum AttackerMember {
+    Fixed(CallGroup),
+    Neural(AttackerNeural),
+    // AttackerFuzz with the default config and this seed, the attacker account is an address argument
+    Fuzz(u64),
+}
+
+impl AttackerMember {
+    pub fn attacker(&self, setup: &Setup) -> BoxAttacker {
+        match self {
+            Self::Fixed(group) => BoxAttacker::new(AttackerFixed::new(group.clone())),
+            Self::Neural(x) => BoxAttacker::new(x.clone()),
+            Self::Fuzz(seed) => BoxAttacker::new(
+                AttackerFuzz::new(setup.abis.to_vec(), FuzzConfig::default(), *seed).with_addresses(vec![setup.attacker])
+            ),
+        }
+    }
+}
+
+// policy space response oracles, in the form of double oracle over an empirical zero-sum game
+// entry [a][d] of the meta-game is the payoff of attacker a against defender d plus the false positive cost of d
+pub struct Psro {
+    pub config: PsroConfig,
+    // plays all games, its attacker and defender lists are unused
+    pub arena: Tournament,
+    pub attackers: Vec<AttackerMember>,
+    pub defenders: Vec<DefenderGuard>,
+    // false positive rate of each defender
+    pub false_positives: Vec<f64>,
+    pub matrix: Vec<Vec<f64>>,
+    pub iteration: usize,
+    rng: StdRng,
+}
+
+impl Psro {
+    // start from an idle AttackerFixed and a permissive guard
+    pub fn new(arena: Tournament, config: PsroConfig) -> Self {
+        let rng = StdRng::seed_from_u64(config.seed);
+        let mut psro = Self {
+            config, arena, attackers: Vec::new(), defenders: Vec::new(),
+            false_positives: Vec::new(), matrix: Vec::new(), iteration: 0, rng,
+        };
+        psro.add_attacker(AttackerMember::Fixed(Vec::new()));
+        psro.add_defender(DefenderGuard::permissive());
+        psro
+    }
+    // equilibrium of the current meta-game
+    pub fn solve(&self) -> Equilibrium {
+        solve_lp(&self.matrix)
+    }
+    // mean payoff over the games that succeed, None if every game fails
+    pub fn payoff(&self, attacker: &AttackerMember, defender: &DefenderGuard) -> Option<f64> {
+        self.arena.evaluate(&|s| attacker.attacker(s), &|_| BoxDefender::new(defender.clone()))
+    }
+    // entry of the meta-game, a failed game reverts the move of the attacker, so an attacker that always fails takes nothing
+    fn entry(&self, attacker: &AttackerMember, defender: &DefenderGuard, false_positive_rate: f64) -> f64 {
+        self.payoff(attacker, defender).unwrap_or(0.0) + self.config.false_positive_cost * false_positive_rate
+    }
+    pub fn false_positive_rate(&self, defender: &DefenderGuard) -> f64 {
+        self.arena.false_positive_rate(defender.clone(), self.config.users, self.config.calls)
+    }
+    pub fn add_attacker(&mut self, attacker: AttackerMember) {
+        let row = self.defenders.iter().zip(&self.false_positives)
+            .map(|(d, fp)| self.entry(&attacker, d, *fp))
+            .collect();
+        self.matrix.push(row);
+        self.attackers.push(attacker);
+    }
+    pub fn add_defender(&mut self, defender: DefenderGuard) {
+        let fp = self.false_positive_rate(&defender);
+        let column = self.attackers.iter().map(|a| self.entry(a, &defender, fp)).collect::<Vec<_>>();
+        for (row, x) in self.matrix.iter_mut().zip(column) {
+            row.push(x);
+        }
+        self.false_positives.push(fp);
+        self.defenders.push(defender);
+    }
+
+    // genetic programming against the defender mixture, returns the best attacker and its expected payoff
+    // failed games only rule attackers out of the search, the payoff is that of the meta-game entries
+    pub fn attacker_oracle(&self, mixture: &[f64]) -> (AttackerNeural, f64) {
+        let fitness = |x: &AttackerNeural| -> f64 {
+            let x = AttackerMember::Neural(x.clone());
+            mixture.iter().zip(&self.defenders)
+                .filter(|(y, _)| **y > 0.0)
+                .map(|(y, d)| y * self.payoff(&x, d).unwrap_or(f64::NEG_INFINITY))
+                .sum::<f64>()
+        };
+        let mut gp = Gp::new(GpConfig { seed: self.config.gp.seed.wrapping_add(self.iteration as u64), ..self.config.gp.clone() });
+        gp.run(self.config.generations, &fitness, None);
+        let best = gp.best().unwrap().0.clone();
+        let member = AttackerMember::Neural(best.clone());
+        let payoff = mixture.iter().zip(self.defenders.iter().zip(&self.false_positives))
+            .filter(|(y, _)| **y > 0.0)
+            .map(|(y, (d, fp))| y * self.entry(&member, d, *fp))
+            .sum::<f64>();
+        (best, payoff)
+    }
+    // random search over guards against the attacker mixture, returns the best guard and its expected loss
+    pub fn defender_oracle(&mut self, mixture: &[f64]) -> (DefenderGuard, f64) {
+        let selectors = self.arena.suite.iter()
+            .flat_map(|x| x.abi.functions().map(|f| f.short_signature()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let candidates = (0..self.config.candidates)
+            .map(|_| DefenderGuard::random(&mut self.rng, &selectors))
+            .collect::<Vec<_>>();
+        candidates.into_iter().map(|d| {
+            let loss = mixture.iter().zip(&self.attackers)
+                .filter(|(x, _)| **x > 0.0)
+                .map(|(x, a)| x * self.payoff(a, &d).unwrap_or(0.0))
+                .sum::<f64>();
+            let fp = self.false_positive_rate(&d);
+            (d, loss + self.config.false_positive_cost * fp)
+        }).min_by(|a, b| a.1.total_cmp(&b.1)).unwrap()
+    }
+
+    // one iteration, each side best responds to the equilibrium of the other
+    // returns whether a population grew, if not the equilibrium is within tolerance
+    pub fn step(&mut self) -> bool {
+        let eq = self.solve();
+        let (attacker, payoff) = self.attacker_oracle(&eq.defender);
+        let attacker_grew = payoff > eq.value + self.config.tolerance;
+        if attacker_grew { self.add_attacker(AttackerMember::Neural(attacker)); }
+        let eq = self.solve();
+        let (defender, loss) = self.defender_oracle(&eq.attacker);
+        let defender_grew = loss < eq.value - self.config.tolerance;
+        if defender_grew { self.add_defender(defender); }
+        self.iteration += 1;
+        if let Some(dir) = self.config.checkpoint.as_deref() {
+            self.save(dir).unwrap();
+        }
+        attacker_grew || defender_grew
+    }
+    // run until no population grows, or for a number of iterations
+    pub fn run(&mut self, iterations: usize) -> Equilibrium {
+        for _ in 0..iterations {
+            if !self.step() { break }
+        }
+        self.solve()
+    }
+
+    // fixed attackers as corpora, neural attackers as gp checkpoints, guards as text, and the meta-game
+    pub fn save(&self, dir: &std::path::Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        for (i, attacker) in self.attackers.iter().enumerate() {
+            match attacker {
+                AttackerMember::Fixed(group) => FuzzCorpus { entries: vec![(group.clone(), 0.0)] }
+                    .save(&dir.join(format!("attacker{i}.corpus")))?,
+                AttackerMember::Neural(x) => {
+                    let mut gp = Gp::new(GpConfig { population: 0, ..self.config.gp.clone() });
+                    gp.population.push((x.clone(), None));
+                    gp.save(&dir.join(format!("attacker{i}.gp")))?;
+                }
+                AttackerMember::Fuzz(seed) => std::fs::write(dir.join(format!("attacker{i}.fuzz")), format!("{seed}\n"))?,
+            }
+        }
+        for (j, defender) in self.defenders.iter().enumerate() {
+            defender.save(&dir.join(format!("defender{j}.guard")))?;
+        }
+        let eq = self.solve();
+        let mut meta = format!("iteration {}\nvalue {}\n", self.iteration, eq.value);
+        meta += &format!("attacker {}\n", words(&eq.attacker));
+        meta += &format!("defender {}\n", words(&eq.defender));
+        meta += &format!("false_positives {}\n", words(&self.false_positives));
+        for row in self.matrix.iter() {
+            meta += &format!("row {}\n", words(row));
+        }
+        std::fs::write(dir.join("meta"), meta)
+    }
+}
+
+fn words(xs: &[f64]) -> String {
+    xs.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,92 @@
+use rayon::prelude::*;
+use crate::attackers::AttackerNeural;
+use crate::environment::interfaces::{BoxDefender, Defender};
+use crate::search::{Gp, GpConfig};
+use super::{AttackerMember, Tournament};
+
+// how the attacker looks for its best response to a committed defender
+pub enum AttackerSearch {
+    // AttackerFuzz with seeds 0..seeds
+    Fuzz { seeds: u64 },
+    // genetic programming over AttackerNeural
+    Gp { config: GpConfig, generations: usize },
+}
+
+// a defender commitment with the best response of the attacker to it
+pub struct Commitment<D> {
+    pub defender: D,
+    pub attacker: AttackerMember,
+    // payoff of the best response, the worst case loss of the defender
+    pub payoff: f64,
+    pub false_positive_rate: f64,
+}
+
+// the defender commits first, then the attacker searches for a best response within a budget
+pub struct Stackelberg {
+    // plays all games, its attacker and defender lists are unused
+    pub arena: Tournament,
+    pub search: AttackerSearch,
+    // loss of the defender per unit of false positive rate, measured with benign users
+    pub false_positive_cost: f64,
+    pub users: usize,
+    pub calls: usize,
+}
+
+impl Stackelberg {
+    pub fn new(arena: Tournament, search: AttackerSearch) -> Self {
+        Self { arena, search, false_positive_cost: 0.0, users: 4, calls: 8 }
+    }
+    pub fn false_positive_cost(mut self, cost: f64, users: usize, calls: usize) -> Self {
+        self.false_positive_cost = cost;
+        self.users = users;
+        self.calls = calls;
+        self
+    }
+
+    // mean payoff of an attacker against the defender over the targets and repeats of the arena
+    // failed games are left out, None if every game fails
+    pub fn payoff<D>(&self, attacker: &AttackerMember, defender: &D) -> Option<f64>
+    where D: Defender + Clone + Send + 'static, D::State: 'static {
+        self.arena.evaluate(&|s| attacker.attacker(s), &|_| BoxDefender::new(defender.clone()))
+    }
+
+    // best attacker found against a committed defender, an idle attacker is always a candidate
+    pub fn best_response<D>(&self, defender: D) -> Commitment<D>
+    where D: Defender + Clone + Send + Sync + 'static, D::State: 'static {
+        let idle = AttackerMember::Fixed(Vec::new());
+        let (attacker, payoff) = match &self.search {
+            AttackerSearch::Fuzz { seeds } => (0..*seeds).into_par_iter()
+                .map(AttackerMember::Fuzz)
+                .chain(rayon::iter::once(idle))
+                .filter_map(|x| { let p = self.payoff(&x, &defender)?; Some((x, p)) })
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap(),
+            AttackerSearch::Gp { config, generations } => {
+                let fitness = |x: &AttackerNeural| self.payoff(&AttackerMember::Neural(x.clone()), &defender).unwrap_or(f64::NEG_INFINITY);
+                let mut gp = Gp::new(config.clone());
+                gp.run(*generations, &fitness, None);
+                let (x, p) = gp.best().cloned().unwrap();
+                let p0 = self.payoff(&idle, &defender).unwrap_or(f64::NEG_INFINITY);
+                if p >= p0 { (AttackerMember::Neural(x), p) } else { (idle, p0) }
+            }
+        };
+        let false_positive_rate = if self.false_positive_cost == 0.0 { 0.0 }
+            else { self.arena.false_positive_rate(defender.clone(), self.users, self.calls) };
+        Commitment { defender, attacker, payoff, false_positive_rate }
+    }
+
+    // the defender that minimises its worst case loss, together with every evaluated commitment
+    pub fn optimise<D>(&self, defenders: impl IntoIterator<Item = D>) -> (usize, Vec<Commitment<D>>)
+    where D: Defender + Clone + Send + Sync + 'static, D::State: 'static {
+        let commitments = defenders.into_iter().map(|d| self.best_response(d)).collect::<Vec<_>>();
+        let best = (0..commitments.len())
+            .min_by(|&a, &b| self.loss(&commitments[a]).total_cmp(&self.loss(&commitments[b])))
+            .expect("no defender to optimise over");
+        (best, commitments)
+    }
+
+    // worst case loss of the defender, including the cost of false positives
+    pub fn loss<D>(&self, commitment: &Commitment<D>) -> f64 {
+        commitment.payoff + self.false_positive_cost * commitment.false_positive_rate
+    }
+}
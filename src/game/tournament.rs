@@ -0,0 +1,198 @@
+use ethers::abi::Abi;
+use rayon::prelude::*;
+use revm::primitives::*;
+use crate::environment::Environment;
+use crate::environment::interfaces::{BoxAttacker, BoxDefender, Defender};
+
+// a target contract in the suite
+pub struct Target {
+    pub name: String,
+    pub init_code: Bytes,
+    pub abi: Abi,
+}
+
+// what a strategy knows about its game before the game starts
+pub struct Setup<'a> {
+    // deployed targets and their abis, in the same order
+    pub targets: &'a [(B160, Bytes)],
+    pub abis: &'a [Abi],
+    pub attacker: B160,
+    pub seed: u64,
+}
+
+pub type AttackerFactory = Box<dyn Fn(&Setup) -> BoxAttacker + Send + Sync>;
+pub type DefenderFactory = Box<dyn Fn(&Setup) -> BoxDefender + Send + Sync>;
+
+// every attacker plays against every defender on every target, each game has a single target
+pub struct Tournament {
+    pub attackers: Vec<(String, AttackerFactory)>,
+    pub defenders: Vec<(String, DefenderFactory)>,
+    pub suite: Vec<Target>,
+    // games per attacker, defender and target, each with its own seed
+    pub repeats: usize,
+    pub limit: usize,
+    pub seed: u64,
+}
+
+impl Tournament {
+    pub fn new(suite: Vec<Target>, limit: usize) -> Self {
+        Self { attackers: Vec::new(), defenders: Vec::new(), suite, repeats: 1, limit, seed: 0 }
+    }
+    pub fn attacker(mut self, name: &str, make: impl Fn(&Setup) -> BoxAttacker + Send + Sync + 'static) -> Self {
+        self.attackers.push((name.to_string(), Box::new(make)));
+        self
+    }
+    pub fn defender(mut self, name: &str, make: impl Fn(&Setup) -> BoxDefender + Send + Sync + 'static) -> Self {
+        self.defenders.push((name.to_string(), Box::new(make)));
+        self
+    }
+    pub fn repeats(mut self, repeats: usize) -> Self {
+        self.repeats = repeats;
+        self
+    }
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+    // payoff of attacker a against defender d on target c in repeat r, None if the game fails
+    pub fn play(&self, a: usize, d: usize, c: usize, r: usize) -> Option<f64> {
+        self.game(&self.attackers[a].1, &self.defenders[d].1, c, r)
+    }
+    // payoff of any pair of strategies on target c in repeat r
+    // every pair sees the same addresses in the same repeat, None if the game fails
+    pub fn game(&self, attacker: &dyn Fn(&Setup) -> BoxAttacker, defender: &dyn Fn(&Setup) -> BoxDefender, c: usize, r: usize) -> Option<f64> {
+        let target = &self.suite[c];
+        let seed = self.seed.wrapping_add((c * self.repeats + r) as u64);
+        let mut env = Environment::<BoxAttacker, BoxDefender>::with_seed(self.limit, seed);
+        env.load_contracts(vec![target.init_code.clone()]);
+        let account = env.create_attacker_account();
+        let targets = env.get_contracts().to_vec();
+        let abis = [target.abi.clone()];
+        let setup = Setup { targets: &targets, abis: &abis, attacker: account, seed };
+        env.load_attacker(attacker(&setup));
+        env.load_defender(defender(&setup));
+        env.payoff()
+    }
+    // mean payoff of a pair of strategies over all targets and repeats, failed games are left out
+    // None if no game succeeds
+    pub fn evaluate(&self, attacker: &dyn Fn(&Setup) -> BoxAttacker, defender: &dyn Fn(&Setup) -> BoxDefender) -> Option<f64> {
+        let n = self.suite.len() * self.repeats;
+        let payoffs = (0..n).filter_map(|i| self.game(attacker, defender, i / self.repeats, i % self.repeats)).collect::<Vec<_>>();
+        if payoffs.is_empty() { None } else { Some(payoffs.iter().sum::<f64>() / payoffs.len() as f64) }
+    }
+    // false positive rate of a defender against benign users of all targets, see defenders::false_positive_rate
+    pub fn false_positive_rate<D: Defender>(&self, defender: D, users: usize, calls: usize) -> f64 {
+        let init_codes = self.suite.iter().map(|x| x.init_code.clone()).collect::<Vec<_>>();
+        let abis = self.suite.iter().map(|x| x.abi.clone()).collect::<Vec<_>>();
+        crate::defenders::false_positive_rate(&init_codes, &abis, defender, users, calls, self.seed)
+    }
+    // play all games in parallel
+    pub fn run(&self) -> PayoffMatrix {
+        let (na, nd, nc, nr) = (self.attackers.len(), self.defenders.len(), self.suite.len(), self.repeats);
+        let payoffs = (0..na * nd * nc * nr).into_par_iter()
+            .map(|i| self.play(i / (nd * nc * nr), i / (nc * nr) % nd, i / nr % nc, i % nr))
+            .collect::<Vec<_>>();
+        let mut samples = vec![vec![vec![Vec::new(); nc]; nd]; na];
+        let mut failures = vec![vec![vec![0; nc]; nd]; na];
+        for (i, p) in payoffs.into_iter().enumerate() {
+            let (a, d, c) = (i / (nd * nc * nr), i / (nc * nr) % nd, i / nr % nc);
+            match p {
+                Some(p) => samples[a][d][c].push(p),
+                None => failures[a][d][c] += 1,
+            }
+        }
+        PayoffMatrix {
+            attackers: self.attackers.iter().map(|x| x.0.clone()).collect(),
+            defenders: self.defenders.iter().map(|x| x.0.clone()).collect(),
+            contracts: self.suite.iter().map(|x| x.name.clone()).collect(),
+            samples,
+            failures,
+        }
+    }
+}
+
+// attacker payoffs, samples[a][d][c] holds one payoff per repeat whose game succeeded
+// failures[a][d][c] counts the other repeats, they are left out of means and variances
+#[derive(Clone, Debug)]
+pub struct PayoffMatrix {
+    pub attackers: Vec<String>,
+    pub defenders: Vec<String>,
+    pub contracts: Vec<String>,
+    pub samples: Vec<Vec<Vec<Vec<f64>>>>,
+    pub failures: Vec<Vec<Vec<usize>>>,
+}
+
+impl PayoffMatrix {
+    // failed games over all targets and repeats
+    pub fn failures(&self, a: usize, d: usize) -> usize {
+        self.failures[a][d].iter().sum()
+    }
+    // mean over all targets and repeats
+    pub fn mean(&self, a: usize, d: usize) -> f64 {
+        mean(self.samples[a][d].iter().flatten())
+    }
+    pub fn variance(&self, a: usize, d: usize) -> f64 {
+        variance(self.samples[a][d].iter().flatten())
+    }
+    pub fn contract_mean(&self, a: usize, d: usize, c: usize) -> f64 {
+        mean(self.samples[a][d][c].iter())
+    }
+    pub fn contract_variance(&self, a: usize, d: usize, c: usize) -> f64 {
+        variance(self.samples[a][d][c].iter())
+    }
+    // matrix of means, rows are attackers and columns are defenders
+    pub fn means(&self) -> Vec<Vec<f64>> {
+        (0..self.attackers.len()).map(|a| (0..self.defenders.len()).map(|d| self.mean(a, d)).collect()).collect()
+    }
+    // one row per attacker, defender and target, target `*` is the mean over all targets
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("attacker,defender,contract,mean,variance,samples,failures\n");
+        for (a, attacker) in self.attackers.iter().enumerate() {
+            for (d, defender) in self.defenders.iter().enumerate() {
+                let n = self.samples[a][d].iter().flatten().count();
+                csv += &format!("{},{},*,{},{},{n},{}\n", quote(attacker), quote(defender), self.mean(a, d), self.variance(a, d), self.failures(a, d));
+                for (c, contract) in self.contracts.iter().enumerate() {
+                    let n = self.samples[a][d][c].len();
+                    csv += &format!("{},{},{},{},{},{n},{}\n", quote(attacker), quote(defender), quote(contract),
+                        self.contract_mean(a, d, c), self.contract_variance(a, d, c), self.failures[a][d][c]);
+                }
+            }
+        }
+        csv
+    }
+    pub fn to_json(&self) -> String {
+        let cells = (0..self.attackers.len()).map(|a| (0..self.defenders.len()).map(|d| {
+            let contracts = (0..self.contracts.len()).map(|c| serde_json::json!({
+                "mean": self.contract_mean(a, d, c),
+                "variance": self.contract_variance(a, d, c),
+                "samples": self.samples[a][d][c],
+                "failures": self.failures[a][d][c],
+            })).collect::<Vec<_>>();
+            serde_json::json!({
+                "mean": self.mean(a, d), "variance": self.variance(a, d), "failures": self.failures(a, d), "contracts": contracts,
+            })
+        }).collect::<Vec<_>>()).collect::<Vec<_>>();
+        serde_json::json!({
+            "attackers": self.attackers,
+            "defenders": self.defenders,
+            "contracts": self.contracts,
+            "payoffs": cells,
+        }).to_string()
+    }
+}
+
+fn mean<'a>(xs: impl Iterator<Item = &'a f64>) -> f64 {
+    let (sum, n) = xs.fold((0.0, 0), |(s, n), x| (s + x, n + 1));
+    if n == 0 { 0.0 } else { sum / n as f64 }
+}
+
+// sample variance
+fn variance<'a>(xs: impl Iterator<Item = &'a f64> + Clone) -> f64 {
+    let m = mean(xs.clone());
+    let (sum, n) = xs.fold((0.0, 0), |(s, n), x| (s + (x - m) * (x - m), n + 1));
+    if n < 2 { 0.0 } else { sum / (n - 1) as f64 }
+}
+
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n']) { format!("\"{}\"", field.replace('"', "\"\"")) } else { field.to_string() }
+}
@@ -2,7 +2,10 @@ mod environment;
 mod attackers;
 mod defenders;
 mod utils;
+#[cfg(feature = "neural")]
 mod neural;
+mod search;
+mod game;
 #[cfg(test)]
 mod tests;
 
@@ -0,0 +1,77 @@
+use std::path::Path;
+use tch::*;
+
+// adam over the trainable variables of a var store, with moments that can be saved
+// the optimizers of tch keep their state out of reach, so checkpoints could not resume them
+pub struct Adam {
+    pub lr: f64,
+    pub beta1: f64,
+    pub beta2: f64,
+    pub eps: f64,
+    // number of steps taken
+    pub step: i64,
+    // trainable variables by name, with their first and second moments
+    vars: Vec<(String, Tensor)>,
+    m: Vec<Tensor>,
+    v: Vec<Tensor>,
+}
+
+impl Adam {
+    pub fn new(vs: &nn::VarStore, lr: f64) -> Self {
+        let mut vars = vs.variables().into_iter().filter(|(_, x)| x.requires_grad()).collect::<Vec<_>>();
+        vars.sort_by(|a, b| a.0.cmp(&b.0));
+        let m = vars.iter().map(|(_, x)| x.zeros_like()).collect();
+        let v = vars.iter().map(|(_, x)| x.zeros_like()).collect();
+        Self { lr, beta1: 0.9, beta2: 0.999, eps: 1e-8, step: 0, vars, m, v }
+    }
+    pub fn zero_grad(&mut self) {
+        for (_, x) in self.vars.iter() {
+            let mut grad = x.grad();
+            if grad.defined() { let _ = grad.detach_().zero_(); }
+        }
+    }
+    // scale gradients down so that their joint norm is at most max
+    pub fn clip_grad_norm(&mut self, max: f64) {
+        let grads = self.vars.iter().map(|(_, x)| x.grad()).filter(|g| g.defined()).collect::<Vec<_>>();
+        let norm = no_grad(|| grads.iter().map(|g| g.pow_tensor_scalar(2).sum(Kind::Double).double_value(&[])).sum::<f64>().sqrt());
+        if norm > max {
+            for mut g in grads { let _ = no_grad(|| g.g_mul_scalar_(max / norm)); }
+        }
+    }
+    pub fn step(&mut self) {
+        self.step += 1;
+        let (b1, b2) = (self.beta1, self.beta2);
+        let (c1, c2) = (1.0 - b1.powi(self.step as i32), 1.0 - b2.powi(self.step as i32));
+        no_grad(|| {
+            for (i, (_, x)) in self.vars.iter().enumerate() {
+                let g = x.grad();
+                if !g.defined() { continue }
+                self.m[i] = &self.m[i] * b1 + &g * (1.0 - b1);
+                self.v[i] = &self.v[i] * b2 + g.square() * (1.0 - b2);
+                let update = (&self.m[i] / c1) / ((&self.v[i] / c2).sqrt() + self.eps) * self.lr;
+                let _ = x.shallow_clone().g_sub_(&update);
+            }
+        });
+    }
+    // moments are saved by variable name
+    pub fn save(&self, path: &Path) -> Result<(), TchError> {
+        let mut named = vec![("step".to_string(), Tensor::from(self.step))];
+        for ((name, _), (m, v)) in self.vars.iter().zip(self.m.iter().zip(&self.v)) {
+            named.push((format!("m.{name}"), m.shallow_clone()));
+            named.push((format!("v.{name}"), v.shallow_clone()));
+        }
+        Tensor::save_multi(&named, path)
+    }
+    pub fn load(&mut self, path: &Path) -> Result<(), TchError> {
+        let named = Tensor::load_multi(path)?.into_iter().collect::<std::collections::HashMap<_, _>>();
+        let missing = |name: &str| TchError::FileFormat(format!("optimizer state has no {name}"));
+        self.step = named.get("step").ok_or_else(|| missing("step"))?.int64_value(&[]);
+        for (i, (name, x)) in self.vars.iter().enumerate() {
+            let m = named.get(&format!("m.{name}")).ok_or_else(|| missing(name))?;
+            let v = named.get(&format!("v.{name}")).ok_or_else(|| missing(name))?;
+            self.m[i] = m.to_device(x.device());
+            self.v[i] = v.to_device(x.device());
+        }
+        Ok(())
+    }
+}
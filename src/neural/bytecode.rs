@@ -0,0 +1,108 @@
+use tch::*;
+use tch::nn::Module;
+
+// tokens of runtime bytecode
+// specials, then one token per opcode, then one token per immediate byte of a push
+pub const PAD: i64 = 0;
+pub const BOS: i64 = 1;
+pub const EOS: i64 = 2;
+// replaces the JUMPDEST opcode, so that basic blocks stand out
+pub const JUMPDEST: i64 = 3;
+// precedes a PUSH4 that is compared with EQ, i.e. a function selector in the dispatcher
+pub const SELECTOR: i64 = 4;
+pub const OPCODE: i64 = 8;
+pub const IMMEDIATE: i64 = OPCODE + 256;
+pub const BYTECODE_VOCAB: i64 = IMMEDIATE + 256;
+
+pub struct BytecodeTokenizer {
+    // upper limit of tokens per contract, including BOS and EOS, which are always kept
+    pub max_len: usize,
+    // upper limit of immediate bytes kept per push, the low order bytes are dropped
+    pub max_immediate: usize,
+}
+
+impl Default for BytecodeTokenizer {
+    fn default() -> Self {
+        Self { max_len: 1024, max_immediate: 32 }
+    }
+}
+
+impl BytecodeTokenizer {
+    pub fn tokenize(&self, code: &[u8]) -> Vec<i64> {
+        let max_len = self.max_len.max(2);
+        let mut tokens = vec![BOS];
+        let mut pc = 0;
+        while pc < code.len() && tokens.len() + 1 < max_len {
+            let op = code[pc];
+            let n = push_len(op);
+            let immediate = &code[(pc + 1).min(code.len())..(pc + 1 + n).min(code.len())];
+            if op == 0x5b {
+                tokens.push(JUMPDEST);
+            } else {
+                if n == 4 && code.get(pc + 5) == Some(&0x14) { tokens.push(SELECTOR); }
+                tokens.push(OPCODE + op as i64);
+                tokens.extend(immediate.iter().take(self.max_immediate).map(|b| IMMEDIATE + *b as i64));
+            }
+            pc += 1 + n;
+        }
+        tokens.truncate(max_len - 1);
+        tokens.push(EOS);
+        tokens
+    }
+    // token ids [b, t] of several contracts, padded with PAD
+    pub fn batch(&self, codes: &[&[u8]], device: Device) -> Tensor {
+        let tokens = codes.iter().map(|code| self.tokenize(code)).collect::<Vec<_>>();
+        let t = tokens.iter().map(|x| x.len()).max().unwrap_or(0);
+        let padded = tokens.into_iter()
+            .flat_map(|mut x| { x.resize(t, PAD); x })
+            .collect::<Vec<_>>();
+        Tensor::from_slice(&padded).reshape([codes.len() as i64, t as i64]).to_device(device)
+    }
+}
+
+// bytecode tokens of every target, each cut to code_len tokens, then BOS to start the game history
+pub fn code_prefix(contracts: &[(revm::primitives::B160, revm::primitives::Bytes)], code_len: usize) -> Vec<i64> {
+    let tokenizer = BytecodeTokenizer { max_len: code_len, ..Default::default() };
+    let mut tokens = contracts.iter().flat_map(|(_, code)| tokenizer.tokenize(code)).collect::<Vec<_>>();
+    tokens.push(BOS);
+    tokens
+}
+
+// selectors compared in the dispatcher, in order of appearance
+pub fn selectors(code: &[u8]) -> Vec<[u8; 4]> {
+    let mut selectors = Vec::new();
+    let mut pc = 0;
+    while pc < code.len() {
+        let n = push_len(code[pc]);
+        if n == 4 && pc + 5 < code.len() && code[pc + 5] == 0x14 {
+            selectors.push(code[pc + 1..pc + 5].try_into().unwrap());
+        }
+        pc += 1 + n;
+    }
+    selectors
+}
+
+fn push_len(op: u8) -> usize {
+    if (0x60..=0x7f).contains(&op) { (op - 0x5f) as usize } else { 0 }
+}
+
+// embedding of bytecode tokens, the sum of a token embedding and an embedding of its class
+// classes are padding, special, opcode and immediate
+#[derive(Debug)]
+pub struct BytecodeEmbedding {
+    wte: nn::Embedding,
+    wce: nn::Embedding,
+}
+
+impl BytecodeEmbedding {
+    pub fn new(vs: nn::Path, n_embd: i64) -> Self {
+        let wte = nn::embedding(&vs / "wte", BYTECODE_VOCAB, n_embd, Default::default());
+        let wce = nn::embedding(&vs / "wce", 4, n_embd, Default::default());
+        Self { wte, wce }
+    }
+    // embedded tokens [b, t, n_embd] of token ids [b, t]
+    pub fn forward(&self, idx: &Tensor) -> Tensor {
+        let class = idx.ne(PAD).to_kind(Kind::Int64) + idx.ge(OPCODE).to_kind(Kind::Int64) + idx.ge(IMMEDIATE).to_kind(Kind::Int64);
+        self.wte.forward(idx) + self.wce.forward(&class)
+    }
+}
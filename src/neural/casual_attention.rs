@@ -9,6 +9,29 @@ pub struct CausalAttention {
     device: Device,
 }
 
+// keys and values [b, n_head, t, head_size] of the steps seen so far, for incremental decoding
+// sequences of a batch share their length
+#[derive(Debug, Default)]
+pub struct KvCache {
+    kv: Option<(Tensor, Tensor)>,
+}
+
+impl KvCache {
+    pub fn len(&self) -> i64 {
+        self.kv.as_ref().map(|(k, _)| k.size()[2]).unwrap_or(0)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn clear(&mut self) {
+        self.kv = None;
+    }
+    // keep the given sequences of the batch, in the given order
+    pub fn index_select(&self, batch: &Tensor) -> Self {
+        Self { kv: self.kv.as_ref().map(|(k, v)| (k.index_select(0, batch), v.index_select(0, batch))) }
+    }
+}
+
 // copied from tch-rs repository
 impl CausalAttention {
     pub fn new(vs: nn::Path, n_head: i64, n_embd: i64) -> Self {
@@ -39,6 +62,37 @@ impl CausalAttention {
         rope.reshape(&dims)
     }
 
+    // attention of s new steps [b, s, c] over the cached steps and themselves
+    // freqs_cis holds the frequencies of the new positions, the cache grows by s
+    pub fn forward_cached(&self, x: &Tensor, freqs_cis: &Tensor, cache: &mut KvCache) -> Tensor {
+        use tch::nn::Module;
+        let (b, s, c) = x.size3().unwrap();
+        let kind = x.kind();
+        let qkv = self.c_attn.forward(x);
+        let n_embd = self.n_embd;
+        let target_dim = [b, s, self.n_head, c / self.n_head];
+        let q = qkv.slice(2, 0, n_embd, 1).reshape(target_dim).transpose(1, 2);
+        let k = qkv.slice(2, n_embd, 2 * n_embd, 1).reshape(target_dim).transpose(1, 2);
+        let v = qkv.slice(2, 2 * n_embd, 3 * n_embd, 1).reshape(target_dim).transpose(1, 2);
+        let q = self.apply_rotary_emb(&q, freqs_cis);
+        let k = self.apply_rotary_emb(&k, freqs_cis);
+        let p = cache.len();
+        let (k, v) = match cache.kv.take() {
+            Some((pk, pv)) => (Tensor::cat(&[pk, k], 2), Tensor::cat(&[pv, v], 2)),
+            None => (k, v),
+        };
+        let att: Tensor = q.matmul(&k.transpose(-2, -1)) / ((c / self.n_head) as f64).sqrt();
+        // new step i sees the cached steps and new steps up to i
+        let att = if s > 1 {
+            let mask = Tensor::ones([s, p + s], (kind, self.device)).tril(p).reshape([1, 1, s, p + s]);
+            att.masked_fill(&mask.eq(0.), f64::NEG_INFINITY)
+        } else { att };
+        let y = att.softmax(-1, kind).matmul(&v);
+        cache.kv = Some((k, v));
+        let y = y.transpose(1, 2).reshape([b, s, c]);
+        self.c_proj.forward(&y)
+    }
+
     pub fn forward(&self, x: &Tensor, freqs_cis: &Tensor) -> Tensor {
         use tch::nn::Module;
         let (b, t, c) = x.size3().unwrap();
@@ -0,0 +1,23 @@
+use tch::{nn, Device};
+
+// device of neural models, from ETH_GAME_DEVICE (`cpu`, `cuda`, `cuda:<n>` or `mps`), cpu if unset
+pub fn device() -> Device {
+    match std::env::var("ETH_GAME_DEVICE") {
+        Ok(name) => parse_device(&name).unwrap_or_else(|| panic!("unknown device {name}")),
+        Err(_) => Device::Cpu,
+    }
+}
+
+// an empty var store on device(), for models to build into or checkpoints to load into
+pub fn var_store() -> nn::VarStore {
+    nn::VarStore::new(device())
+}
+
+pub fn parse_device(name: &str) -> Option<Device> {
+    match name.trim().to_lowercase().as_str() {
+        "cpu" => Some(Device::Cpu),
+        "mps" => Some(Device::Mps),
+        "cuda" => Some(Device::Cuda(0)),
+        x => x.strip_prefix("cuda:")?.parse().ok().map(Device::Cuda),
+    }
+}
@@ -1,2 +1,12 @@
 mod casual_attention;
-pub use casual_attention::*;
\ No newline at end of file
+pub use casual_attention::*;
+mod model;
+pub use model::*;
+mod bytecode;
+pub use bytecode::*;
+mod trace;
+pub use trace::*;
+mod adam;
+pub use adam::*;
+mod device;
+pub use device::*;
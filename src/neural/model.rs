@@ -0,0 +1,174 @@
+use tch::*;
+use tch::nn::Module;
+use super::{CausalAttention, KvCache};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    pub vocab_size: i64,
+    pub n_layer: i64,
+    pub n_head: i64,
+    pub n_embd: i64,
+    // longest sequence the rotary frequencies are computed for
+    pub block_size: i64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self { vocab_size: 512, n_layer: 4, n_head: 4, n_embd: 128, block_size: 1024 }
+    }
+}
+
+#[derive(Debug)]
+struct Mlp {
+    c_fc: nn::Linear,
+    c_proj: nn::Linear,
+}
+
+impl Mlp {
+    fn new(vs: nn::Path, n_embd: i64) -> Self {
+        let c_fc = nn::linear(&vs / "c_fc", n_embd, 4 * n_embd, Default::default());
+        let c_proj = nn::linear(&vs / "c_proj", 4 * n_embd, n_embd, Default::default());
+        Self { c_fc, c_proj }
+    }
+    fn forward(&self, x: &Tensor) -> Tensor {
+        self.c_proj.forward(&self.c_fc.forward(x).gelu("none"))
+    }
+}
+
+// key/value caches of every block of a model
+#[derive(Debug, Default)]
+pub struct ModelCache {
+    pub blocks: Vec<KvCache>,
+}
+
+impl ModelCache {
+    // number of steps seen
+    pub fn len(&self) -> i64 {
+        self.blocks.first().map(|x| x.len()).unwrap_or(0)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    // keep the given sequences of the batch, in the given order
+    pub fn index_select(&self, batch: &Tensor) -> Self {
+        Self { blocks: self.blocks.iter().map(|x| x.index_select(batch)).collect() }
+    }
+}
+
+// pre-norm transformer block
+#[derive(Debug)]
+pub struct Block {
+    ln_1: nn::LayerNorm,
+    attn: CausalAttention,
+    ln_2: nn::LayerNorm,
+    mlp: Mlp,
+}
+
+impl Block {
+    pub fn new(vs: nn::Path, config: &Config) -> Self {
+        let ln_1 = nn::layer_norm(&vs / "ln_1", vec![config.n_embd], Default::default());
+        let attn = CausalAttention::new(&vs / "attn", config.n_head, config.n_embd);
+        let ln_2 = nn::layer_norm(&vs / "ln_2", vec![config.n_embd], Default::default());
+        let mlp = Mlp::new(&vs / "mlp", config.n_embd);
+        Self { ln_1, attn, ln_2, mlp }
+    }
+    pub fn forward(&self, x: &Tensor, freqs_cis: &Tensor) -> Tensor {
+        let x = self.attn.forward(&self.ln_1.forward(x), freqs_cis) + x;
+        self.mlp.forward(&self.ln_2.forward(&x)) + x
+    }
+    pub fn forward_cached(&self, x: &Tensor, freqs_cis: &Tensor, cache: &mut KvCache) -> Tensor {
+        let x = self.attn.forward_cached(&self.ln_1.forward(x), freqs_cis, cache) + x;
+        self.mlp.forward(&self.ln_2.forward(&x)) + x
+    }
+}
+
+// gpt-style decoder over token ids with rotary position embedding
+#[derive(Debug)]
+pub struct Model {
+    pub config: Config,
+    wte: nn::Embedding,
+    blocks: Vec<Block>,
+    ln_f: nn::LayerNorm,
+    lm_head: nn::Linear,
+    freqs_cis: Tensor,
+}
+
+impl Model {
+    pub fn new(vs: nn::Path, config: Config) -> Self {
+        let wte = nn::embedding(&vs / "wte", config.vocab_size, config.n_embd, Default::default());
+        let blocks = (0..config.n_layer).map(|i| Block::new(&vs / "h" / i, &config)).collect();
+        let ln_f = nn::layer_norm(&vs / "ln_f", vec![config.n_embd], Default::default());
+        let c = nn::LinearConfig { bias: false, ..Default::default() };
+        let lm_head = nn::linear(&vs / "lm_head", config.n_embd, config.vocab_size, c);
+        let freqs_cis = freqs_cis(&config, vs.device());
+        Self { config, wte, blocks, ln_f, lm_head, freqs_cis }
+    }
+    pub fn device(&self) -> Device {
+        self.freqs_cis.device()
+    }
+    // final hidden states [b, t, n_embd] of token ids [b, t]
+    pub fn hidden(&self, idx: &Tensor) -> Tensor {
+        self.hidden_embedded(&self.wte.forward(idx))
+    }
+    // final hidden states of already embedded inputs [b, t, n_embd]
+    pub fn hidden_embedded(&self, x: &Tensor) -> Tensor {
+        let t = x.size()[1];
+        assert!(t <= self.config.block_size, "sequence of {t} tokens is longer than block size");
+        let freqs_cis = self.freqs_cis.narrow(2, 0, t);
+        let x = self.blocks.iter().fold(x.shallow_clone(), |x, block| block.forward(&x, &freqs_cis));
+        self.ln_f.forward(&x)
+    }
+    // next token logits of hidden states
+    pub fn logits(&self, hidden: &Tensor) -> Tensor {
+        self.lm_head.forward(hidden)
+    }
+    // next token logits [b, t, vocab_size] of token ids [b, t]
+    pub fn forward(&self, idx: &Tensor) -> Tensor {
+        self.lm_head.forward(&self.hidden(idx))
+    }
+    // final hidden states [b, s, n_embd] of s new token ids [b, s] following the steps in cache
+    pub fn hidden_cached(&self, idx: &Tensor, cache: &mut ModelCache) -> Tensor {
+        let (p, s) = (cache.len(), idx.size()[1]);
+        assert!(p + s <= self.config.block_size, "sequence of {} tokens is longer than block size", p + s);
+        cache.blocks.resize_with(self.blocks.len(), KvCache::default);
+        let freqs_cis = self.freqs_cis.narrow(2, p, s);
+        let x = self.blocks.iter().zip(cache.blocks.iter_mut())
+            .fold(self.wte.forward(idx), |x, (block, cache)| block.forward_cached(&x, &freqs_cis, cache));
+        self.ln_f.forward(&x)
+    }
+    // next token logits [b, s, vocab_size] of s new token ids [b, s] following the steps in cache
+    pub fn forward_cached(&self, idx: &Tensor, cache: &mut ModelCache) -> Tensor {
+        self.lm_head.forward(&self.hidden_cached(idx, cache))
+    }
+}
+
+// rotary frequencies [1, 1, block_size, head_size / 2, 2] as (cos, sin) pairs, the layout CausalAttention expects
+pub fn freqs_cis(config: &Config, device: Device) -> Tensor {
+    let head_size = config.n_embd / config.n_head;
+    let theta = (0..head_size).step_by(2)
+        .map(|i| 1f32 / 10000f32.powf(i as f32 / head_size as f32))
+        .collect::<Vec<_>>();
+    let arange = (0..config.block_size).map(|i| i as f32).collect::<Vec<_>>();
+    let idx_theta = Tensor::from_slice(&arange).outer(&Tensor::from_slice(&theta));
+    let shape = [1, 1, config.block_size, head_size / 2, 1];
+    Tensor::cat(&[idx_theta.cos().reshape(shape), idx_theta.sin().reshape(shape)], -1).to_device(device)
+}
+
+// predicts the final payoff of the attacker, divided by a scale, from the hidden state of each prefix
+#[derive(Debug)]
+pub struct ValueHead {
+    c_fc: nn::Linear,
+    c_proj: nn::Linear,
+}
+
+impl ValueHead {
+    pub fn new(vs: nn::Path, n_embd: i64) -> Self {
+        let c_fc = nn::linear(&vs / "c_fc", n_embd, n_embd, Default::default());
+        let c_proj = nn::linear(&vs / "c_proj", n_embd, 1, Default::default());
+        Self { c_fc, c_proj }
+    }
+    // values [b, t] of hidden states [b, t, n_embd]
+    pub fn forward(&self, hidden: &Tensor) -> Tensor {
+        self.c_proj.forward(&self.c_fc.forward(hidden).gelu("none")).squeeze_dim(-1)
+    }
+}
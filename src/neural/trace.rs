@@ -0,0 +1,229 @@
+use revm::interpreter::*;
+use revm::primitives::*;
+use tch::{Device, Tensor};
+use crate::attackers::Event;
+use super::{selectors, BOS, BYTECODE_VOCAB, EOS, IMMEDIATE};
+
+// tokens of game events, after the bytecode tokens so that one model can read both
+// data bytes reuse the immediate byte tokens of bytecode
+pub const CALL: i64 = BYTECODE_VOCAB;
+pub const RETURN: i64 = BYTECODE_VOCAB + 1;
+pub const CHECK: i64 = BYTECODE_VOCAB + 2;
+// stop making calls
+pub const STOP: i64 = BYTECODE_VOCAB + 3;
+pub const STATUS_OK: i64 = BYTECODE_VOCAB + 4;
+pub const STATUS_REVERT: i64 = BYTECODE_VOCAB + 5;
+pub const STATUS_ERROR: i64 = BYTECODE_VOCAB + 6;
+pub const WORD_ZERO: i64 = BYTECODE_VOCAB + 7;
+pub const WORD_ATTACKER: i64 = BYTECODE_VOCAB + 8;
+// an address that is neither a target nor the attacker, followed by its 20 bytes
+pub const ADDRESS_OTHER: i64 = BYTECODE_VOCAB + 9;
+// followed by the 4 bytes of the selector
+pub const UNKNOWN_SELECTOR: i64 = BYTECODE_VOCAB + 10;
+// calldata shorter than a selector, e.g. a plain transfer
+pub const NO_SELECTOR: i64 = BYTECODE_VOCAB + 11;
+pub const CALLER_ATTACKER: i64 = BYTECODE_VOCAB + 12;
+pub const CALLER_TARGET: i64 = BYTECODE_VOCAB + 13;
+pub const CALLER_OTHER: i64 = BYTECODE_VOCAB + 14;
+pub const MAX_TARGETS: usize = 16;
+pub const MAX_SELECTORS: usize = 64;
+// index of a target address
+pub const TARGET: i64 = BYTECODE_VOCAB + 16;
+// index of a selector in the dispatchers of the targets
+pub const SELECTOR_INDEX: i64 = TARGET + MAX_TARGETS as i64;
+// bit length of a value, 0 to 256
+pub const VALUE: i64 = SELECTOR_INDEX + MAX_SELECTORS as i64;
+// a word with n significant bytes, followed by those bytes
+pub const WORD_BYTES: i64 = VALUE + 257;
+pub const VOCAB: i64 = WORD_BYTES + 33;
+
+// what the attacker or defender sees of a game
+#[derive(Clone, Debug, PartialEq)]
+pub enum TraceEvent {
+    Call { address: B160, value: U256, input: Bytes },
+    Return { status: InstructionResult, out: Bytes },
+    Check { caller: B160, address: B160, value: U256, input: Bytes },
+}
+
+impl TraceEvent {
+    // call inputs seen by Defender::check
+    pub fn check(inputs: &CallInputs) -> Self {
+        Self::Check { caller: inputs.context.caller, address: inputs.contract, value: inputs.transfer.value, input: inputs.input.clone() }
+    }
+}
+
+// turns game events into tokens and call tokens back into calls
+// addresses are encoded relative to the targets and the attacker of the game
+pub struct TraceEncoder {
+    pub targets: Vec<B160>,
+    pub attacker: B160,
+    // selectors found in the dispatchers of the targets
+    pub selectors: Vec<[u8; 4]>,
+    // upper limit of calldata or output words per event
+    pub max_words: usize,
+}
+
+impl TraceEncoder {
+    pub fn new(contracts: &[(B160, Bytes)], attacker: B160) -> Self {
+        let mut all = Vec::new();
+        for s in contracts.iter().flat_map(|(_, code)| selectors(code)) {
+            if !all.contains(&s) && all.len() < MAX_SELECTORS { all.push(s); }
+        }
+        let targets = contracts.iter().map(|x| x.0).take(MAX_TARGETS).collect();
+        Self { targets, attacker, selectors: all, max_words: 8 }
+    }
+
+    pub fn encode_event(&self, event: &TraceEvent) -> Vec<i64> {
+        let mut tokens = Vec::new();
+        match event {
+            TraceEvent::Call { address, value, input } => {
+                tokens.push(CALL);
+                self.encode_call_body(&mut tokens, *address, *value, input);
+            }
+            TraceEvent::Return { status, out } => {
+                tokens.push(RETURN);
+                tokens.push(status_token(*status));
+                self.encode_words(&mut tokens, out);
+            }
+            TraceEvent::Check { caller, address, value, input } => {
+                tokens.push(CHECK);
+                tokens.push(if *caller == self.attacker { CALLER_ATTACKER }
+                    else if self.targets.contains(caller) { CALLER_TARGET } else { CALLER_OTHER });
+                self.encode_call_body(&mut tokens, *address, *value, input);
+            }
+        }
+        tokens
+    }
+    // a game history, starting with BOS
+    pub fn encode(&self, events: &[TraceEvent]) -> Vec<i64> {
+        std::iter::once(BOS).chain(events.iter().flat_map(|e| self.encode_event(e))).collect()
+    }
+    // tokens of what an attacker saw in a recording, exits are dropped
+    pub fn encode_recording(&self, events: &[Event]) -> Vec<i64> {
+        let events = events.iter().filter_map(|event| match event {
+            Event::Call { address, value, input, .. } => Some(TraceEvent::Call { address: *address, value: *value, input: input.clone() }),
+            Event::Return { status, out, .. } => {
+                let status = match status.as_str() {
+                    "Stop" => InstructionResult::Stop,
+                    "Return" => InstructionResult::Return,
+                    "SelfDestruct" => InstructionResult::SelfDestruct,
+                    "Revert" => InstructionResult::Revert,
+                    _ => InstructionResult::OutOfGas,
+                };
+                Some(TraceEvent::Return { status, out: out.clone() })
+            }
+            Event::Exit { .. } => None,
+        }).collect::<Vec<_>>();
+        self.encode(&events)
+    }
+    // token ids [1, t]
+    pub fn tensor(&self, tokens: &[i64], device: Device) -> Tensor {
+        Tensor::from_slice(tokens).reshape([1, tokens.len() as i64]).to_device(device)
+    }
+
+    fn encode_call_body(&self, tokens: &mut Vec<i64>, address: B160, value: U256, input: &Bytes) {
+        self.encode_address(tokens, address);
+        tokens.push(VALUE + value.bit_len() as i64);
+        if input.len() < 4 {
+            tokens.push(NO_SELECTOR);
+            return;
+        }
+        let selector: [u8; 4] = input[..4].try_into().unwrap();
+        match self.selectors.iter().position(|s| *s == selector) {
+            Some(i) => tokens.push(SELECTOR_INDEX + i as i64),
+            None => {
+                tokens.push(UNKNOWN_SELECTOR);
+                tokens.extend(selector.iter().map(|b| IMMEDIATE + *b as i64));
+            }
+        }
+        self.encode_words(tokens, &input[4..]);
+    }
+    fn encode_address(&self, tokens: &mut Vec<i64>, address: B160) {
+        match self.targets.iter().position(|x| *x == address) {
+            Some(i) => tokens.push(TARGET + i as i64),
+            None if address == self.attacker => tokens.push(WORD_ATTACKER),
+            None => {
+                tokens.push(ADDRESS_OTHER);
+                tokens.extend(address.as_bytes().iter().map(|b| IMMEDIATE + *b as i64));
+            }
+        }
+    }
+    // 32 byte words, a partial last word is padded on the right
+    fn encode_words(&self, tokens: &mut Vec<i64>, data: &[u8]) {
+        for chunk in data.chunks(32).take(self.max_words) {
+            let mut word = [0u8; 32];
+            word[..chunk.len()].copy_from_slice(chunk);
+            let address = B160::from_slice(&word[12..]);
+            if word.iter().all(|b| *b == 0) {
+                tokens.push(WORD_ZERO);
+            } else if word[..12].iter().all(|b| *b == 0) && (address == self.attacker || self.targets.contains(&address)) {
+                self.encode_address(tokens, address);
+            } else {
+                let start = word.iter().position(|b| *b != 0).unwrap();
+                tokens.push(WORD_BYTES + (32 - start) as i64);
+                tokens.extend(word[start..].iter().map(|b| IMMEDIATE + *b as i64));
+            }
+        }
+    }
+
+    // decode call tokens, with or without the leading CALL, up to STOP, EOS or the next event
+    // values are quantised to the power of two of their bit length, None if the tokens are not a call
+    pub fn decode_call(&self, tokens: &[i64]) -> Option<(B160, U256, Bytes)> {
+        let mut tokens = tokens.iter().copied().peekable();
+        if tokens.peek() == Some(&CALL) { tokens.next(); }
+        let to = self.decode_address(&mut tokens)?;
+        let value = match tokens.next()? {
+            t if (VALUE..VALUE + 257).contains(&t) => {
+                let bits = (t - VALUE) as usize;
+                if bits == 0 { U256::ZERO } else { U256::from(1) << (bits - 1) }
+            }
+            _ => return None,
+        };
+        let mut input = Vec::new();
+        match tokens.next()? {
+            NO_SELECTOR => return Some((to, value, Bytes::default())),
+            UNKNOWN_SELECTOR => for _ in 0..4 { input.push(byte(&mut tokens)?) },
+            t if (SELECTOR_INDEX..SELECTOR_INDEX + MAX_SELECTORS as i64).contains(&t) => {
+                input.extend_from_slice(self.selectors.get((t - SELECTOR_INDEX) as usize)?);
+            }
+            _ => return None,
+        }
+        while let Some(&t) = tokens.peek() {
+            let mut word = [0u8; 32];
+            match t {
+                STOP | EOS | CALL | RETURN | CHECK => break,
+                WORD_ZERO => { tokens.next(); }
+                t if (WORD_BYTES + 1..WORD_BYTES + 33).contains(&t) => {
+                    tokens.next();
+                    let n = (t - WORD_BYTES) as usize;
+                    for b in word[32 - n..].iter_mut() { *b = byte(&mut tokens)?; }
+                }
+                _ => word[12..].copy_from_slice(self.decode_address(&mut tokens)?.as_bytes()),
+            }
+            input.extend_from_slice(&word);
+        }
+        Some((to, value, input.into()))
+    }
+    fn decode_address(&self, tokens: &mut Tokens) -> Option<B160> {
+        match tokens.next()? {
+            t if (TARGET..TARGET + MAX_TARGETS as i64).contains(&t) => self.targets.get((t - TARGET) as usize).copied(),
+            WORD_ATTACKER => Some(self.attacker),
+            ADDRESS_OTHER => (0..20).map(|_| byte(tokens)).collect::<Option<Vec<_>>>().map(|x| B160::from_slice(&x)),
+            _ => None,
+        }
+    }
+}
+
+type Tokens<'a> = std::iter::Peekable<std::iter::Copied<std::slice::Iter<'a, i64>>>;
+
+fn byte(tokens: &mut Tokens) -> Option<u8> {
+    tokens.next().filter(|t| (IMMEDIATE..IMMEDIATE + 256).contains(t)).map(|t| (t - IMMEDIATE) as u8)
+}
+
+fn status_token(status: InstructionResult) -> i64 {
+    match status {
+        InstructionResult::Stop | InstructionResult::Return | InstructionResult::SelfDestruct => STATUS_OK,
+        InstructionResult::Revert => STATUS_REVERT,
+        _ => STATUS_ERROR,
+    }
+}
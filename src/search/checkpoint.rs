@@ -0,0 +1,183 @@
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use serde_json::{json, Value};
+use tch::nn;
+use crate::neural::{var_store, Config};
+use super::{Algorithm, PgConfig, PolicyGradient, Schedule};
+
+// version of the checkpoint layout, checkpoints of other versions are refused
+pub const CHECKPOINT_VERSION: u64 = 1;
+
+// an entry of the manifest
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckpointEntry {
+    pub id: usize,
+    pub update: usize,
+    // checkpoint this one was trained from, e.g. the resumed one
+    pub parent: Option<usize>,
+    pub tag: String,
+}
+
+// a directory of checkpoints with a manifest, each checkpoint lives in a subdirectory named by its id
+// a checkpoint holds model weights, adam moments and a state file with configs, update, baseline and seed
+// sampling randomness is derived from the seed and the update, so they are the random state of training
+pub struct Checkpoints {
+    pub dir: PathBuf,
+}
+
+impl Checkpoints {
+    pub fn open(dir: &Path) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        Ok(Self { dir: dir.to_path_buf() })
+    }
+    pub fn path(&self, id: usize) -> PathBuf {
+        self.dir.join(format!("{id:06}"))
+    }
+    pub fn manifest(&self) -> std::io::Result<Vec<CheckpointEntry>> {
+        let path = self.dir.join("manifest.json");
+        if !path.exists() { return Ok(Vec::new()) }
+        let manifest = serde_json::from_str::<Value>(&std::fs::read_to_string(path)?).map_err(invalid)?;
+        check_version(&manifest)?;
+        manifest["checkpoints"].as_array().ok_or_else(|| invalid("manifest without checkpoints"))?
+            .iter()
+            .map(|x| Ok(CheckpointEntry {
+                id: x["id"].as_u64().ok_or_else(|| invalid("checkpoint without id"))? as usize,
+                update: x["update"].as_u64().unwrap_or(0) as usize,
+                parent: x["parent"].as_u64().map(|x| x as usize),
+                tag: x["tag"].as_str().unwrap_or("").to_string(),
+            }))
+            .collect()
+    }
+    pub fn latest(&self) -> std::io::Result<Option<CheckpointEntry>> {
+        Ok(self.manifest()?.into_iter().max_by_key(|x| x.id))
+    }
+
+    // save a new checkpoint, returns its id
+    pub fn save(&self, vs: &nn::VarStore, model: &Config, trainer: &PolicyGradient, tag: &str, parent: Option<usize>) -> std::io::Result<usize> {
+        let mut manifest = self.manifest()?;
+        let id = manifest.iter().map(|x| x.id + 1).max().unwrap_or(0);
+        let path = self.path(id);
+        std::fs::create_dir_all(&path)?;
+        vs.save(path.join("model.ot")).map_err(invalid)?;
+        trainer.opt.save(&path.join("optimizer.ot")).map_err(invalid)?;
+        let state = json!({
+            "version": CHECKPOINT_VERSION,
+            "model": model_json(model),
+            "trainer": trainer_json(&trainer.config),
+            "update": trainer.update,
+            "baseline": trainer.baseline,
+        });
+        std::fs::write(path.join("state.json"), state.to_string())?;
+        // the manifest is written last, so an interrupted save leaves no entry
+        manifest.push(CheckpointEntry { id, update: trainer.update, parent, tag: tag.to_string() });
+        let entries = manifest.iter().map(|x| json!({ "id": x.id, "update": x.update, "parent": x.parent, "tag": x.tag })).collect::<Vec<_>>();
+        let tmp = self.dir.join("manifest.json.tmp");
+        std::fs::write(&tmp, json!({ "version": CHECKPOINT_VERSION, "checkpoints": entries }).to_string())?;
+        std::fs::rename(tmp, self.dir.join("manifest.json"))?;
+        Ok(id)
+    }
+
+    // configs of a checkpoint, to build the model and trainer before load
+    pub fn config(&self, id: usize) -> std::io::Result<(Config, PgConfig)> {
+        let state = self.state(id)?;
+        Ok((parse_model(&state["model"])?, parse_trainer(&state["trainer"])?))
+    }
+    // restore weights into vs and the optimizer, update and baseline into trainer
+    pub fn load(&self, id: usize, vs: &mut nn::VarStore, trainer: &mut PolicyGradient) -> std::io::Result<()> {
+        let state = self.state(id)?;
+        let path = self.path(id);
+        vs.load(path.join("model.ot")).map_err(invalid)?;
+        trainer.opt.load(&path.join("optimizer.ot")).map_err(invalid)?;
+        trainer.config = parse_trainer(&state["trainer"])?;
+        trainer.update = state["update"].as_u64().ok_or_else(|| invalid("state without update"))? as usize;
+        trainer.baseline = state["baseline"].as_f64();
+        Ok(())
+    }
+    // a var store on neural::device() with the weights of a checkpoint, what build made of it and the restored trainer
+    // build creates every trainable variable, e.g. the model and its critic, before the trainer collects them
+    pub fn resume<M>(&self, id: usize, build: impl FnOnce(nn::Path, Config) -> M) -> std::io::Result<(nn::VarStore, M, PolicyGradient)> {
+        let (model, config) = self.config(id)?;
+        let mut vs = var_store();
+        let built = build(vs.root(), model);
+        let mut trainer = PolicyGradient::new(&vs, config);
+        self.load(id, &mut vs, &mut trainer)?;
+        Ok((vs, built, trainer))
+    }
+    fn state(&self, id: usize) -> std::io::Result<Value> {
+        let state = serde_json::from_str::<Value>(&std::fs::read_to_string(self.path(id).join("state.json"))?).map_err(invalid)?;
+        check_version(&state)?;
+        Ok(state)
+    }
+}
+
+fn invalid(e: impl ToString) -> Error {
+    Error::new(ErrorKind::InvalidData, e.to_string())
+}
+
+fn check_version(x: &Value) -> std::io::Result<()> {
+    match x["version"].as_u64() {
+        Some(CHECKPOINT_VERSION) => Ok(()),
+        v => Err(invalid(format!("checkpoint version {v:?}, expected {CHECKPOINT_VERSION}"))),
+    }
+}
+
+fn model_json(c: &Config) -> Value {
+    json!({
+        "vocab_size": c.vocab_size, "n_layer": c.n_layer, "n_head": c.n_head,
+        "n_embd": c.n_embd, "block_size": c.block_size,
+    })
+}
+
+fn parse_model(x: &Value) -> std::io::Result<Config> {
+    let get = |key: &str| x[key].as_i64().ok_or_else(|| invalid(format!("model config without {key}")));
+    Ok(Config {
+        vocab_size: get("vocab_size")?, n_layer: get("n_layer")?, n_head: get("n_head")?,
+        n_embd: get("n_embd")?, block_size: get("block_size")?,
+    })
+}
+
+fn trainer_json(c: &PgConfig) -> Value {
+    let algorithm = match c.algorithm {
+        Algorithm::Reinforce => json!({ "reinforce": {} }),
+        Algorithm::Ppo { clip, epochs } => json!({ "ppo": { "clip": clip, "epochs": epochs } }),
+    };
+    let schedule = match c.schedule {
+        Schedule::Constant => json!({ "constant": {} }),
+        Schedule::Linear { end } => json!({ "linear": { "end": end } }),
+        Schedule::Cosine { warmup } => json!({ "cosine": { "warmup": warmup } }),
+    };
+    json!({
+        "algorithm": algorithm, "lr": c.lr, "schedule": schedule, "updates": c.updates, "batch": c.batch,
+        "entropy": c.entropy, "max_grad_norm": c.max_grad_norm, "baseline_decay": c.baseline_decay,
+        "temperature": c.temperature, "value_coef": c.value_coef, "value_scale": c.value_scale, "seed": c.seed,
+    })
+}
+
+fn parse_trainer(x: &Value) -> std::io::Result<PgConfig> {
+    let float = |key: &str| x[key].as_f64().ok_or_else(|| invalid(format!("trainer config without {key}")));
+    let int = |key: &str| x[key].as_u64().ok_or_else(|| invalid(format!("trainer config without {key}")));
+    let algorithm = if x["algorithm"]["reinforce"].is_object() {
+        Algorithm::Reinforce
+    } else {
+        let ppo = &x["algorithm"]["ppo"];
+        let clip = ppo["clip"].as_f64().ok_or_else(|| invalid("bad algorithm"))?;
+        Algorithm::Ppo { clip, epochs: ppo["epochs"].as_u64().ok_or_else(|| invalid("bad algorithm"))? as usize }
+    };
+    let schedule = &x["schedule"];
+    let schedule = if let Some(end) = schedule["linear"]["end"].as_f64() {
+        Schedule::Linear { end }
+    } else if let Some(warmup) = schedule["cosine"]["warmup"].as_u64() {
+        Schedule::Cosine { warmup: warmup as usize }
+    } else {
+        Schedule::Constant
+    };
+    Ok(PgConfig {
+        algorithm, lr: float("lr")?, schedule, updates: int("updates")? as usize, batch: int("batch")? as usize,
+        entropy: float("entropy")?, max_grad_norm: float("max_grad_norm")?, baseline_decay: float("baseline_decay")?,
+        temperature: float("temperature")?,
+        // the critic settings were added without a version bump, version 1 checkpoints from before get the defaults
+        value_coef: x["value_coef"].as_f64().unwrap_or(PgConfig::default().value_coef),
+        value_scale: x["value_scale"].as_f64().unwrap_or(PgConfig::default().value_scale),
+        seed: int("seed")?,
+    })
+}
@@ -0,0 +1,250 @@
+use std::io::{BufRead, Write};
+use std::path::Path;
+use rand::prelude::*;
+use rayon::prelude::*;
+use revm::primitives::Bytes;
+use crate::attackers::AttackerNeural;
+use crate::environment::{interfaces::Defender, Environment};
+
+#[derive(Clone)]
+pub struct GpConfig {
+    // number of individuals in each generation
+    pub population: usize,
+    // number of contestants in a tournament
+    pub tournament: usize,
+    // number of best individuals copied to the next generation unchanged
+    pub elite: usize,
+    // number of best individuals ever seen that are kept in the archive
+    pub archive: usize,
+    // probability that a child is produced by crossover
+    pub crossover_rate: f64,
+    // probability that each script word / projection word is mutated
+    pub mutation_rate: f64,
+    // script length of the initial population
+    pub script_len: usize,
+    // upper limit of script length after mutation and crossover
+    pub max_script_len: usize,
+    pub seed: u64,
+}
+
+impl Default for GpConfig {
+    fn default() -> Self {
+        Self {
+            population: 64, tournament: 4, elite: 2, archive: 16,
+            crossover_rate: 0.7, mutation_rate: 0.02,
+            script_len: 16, max_script_len: 256, seed: 0,
+        }
+    }
+}
+
+// genetic programming over the scripts and projections of AttackerNeural
+pub struct Gp {
+    pub config: GpConfig,
+    pub generation: usize,
+    // current population with fitness, None if not evaluated yet
+    pub population: Vec<(AttackerNeural, Option<f64>)>,
+    // best individuals ever seen, sorted by descending fitness
+    pub archive: Vec<(AttackerNeural, f64)>,
+    // random state of breeding, derived from the seed and the generation so that checkpoints resume exactly
+    rng: StdRng,
+}
+
+// fitness of an attacker as its payoff in a seeded game on contracts, against a defender made by defender
+// failed games have fitness f64::NEG_INFINITY
+pub fn game_fitness<D: Defender>(
+    contracts: Vec<Bytes>,
+    limit: usize,
+    seed: u64,
+    defender: impl Fn() -> D + Sync,
+) -> impl Fn(&AttackerNeural) -> f64 + Sync {
+    move |x| {
+        let mut env = Environment::<AttackerNeural, D>::with_seed(limit, seed);
+        env.load_contracts(contracts.clone());
+        env.create_attacker_account();
+        env.load_attacker(x.clone());
+        env.load_defender(defender());
+        env.payoff().unwrap_or(f64::NEG_INFINITY)
+    }
+}
+
+fn breeding_rng(config: &GpConfig, generation: usize) -> StdRng {
+    StdRng::seed_from_u64(config.seed.wrapping_add(generation as u64 + 1))
+}
+
+impl Gp {
+    pub fn new(config: GpConfig) -> Self {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let population = (0..config.population)
+            .map(|_| (AttackerNeural::random(&mut rng, config.script_len), None))
+            .collect();
+        let rng = breeding_rng(&config, 0);
+        Self { config, generation: 0, population, archive: Vec::new(), rng }
+    }
+
+    // evaluate individuals without fitness in parallel, failed games should map to f64::NEG_INFINITY
+    pub fn evaluate(&mut self, fitness: &(impl Fn(&AttackerNeural) -> f64 + Sync)) {
+        let fresh = self.population.par_iter_mut()
+            .filter(|(_, f)| f.is_none())
+            .map(|(x, f)| { *f = Some(fitness(x)); (x.clone(), f.unwrap()) })
+            .collect::<Vec<_>>();
+        self.archive.extend(fresh);
+        self.archive.sort_by(|a, b| b.1.total_cmp(&a.1));
+        self.archive.dedup_by(|a, b| a.1 == b.1 && same(&a.0, &b.0));
+        self.archive.truncate(self.config.archive);
+    }
+
+    // the best individual ever seen
+    pub fn best(&self) -> Option<&(AttackerNeural, f64)> {
+        self.archive.first()
+    }
+
+    // evaluate the current generation and breed the next one
+    pub fn step(&mut self, fitness: &(impl Fn(&AttackerNeural) -> f64 + Sync)) {
+        self.evaluate(fitness);
+        self.rng = breeding_rng(&self.config, self.generation);
+        let mut ranked = self.population.iter()
+            .map(|(x, f)| (x, f.unwrap()))
+            .collect::<Vec<_>>();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let mut next = ranked.iter()
+            .take(self.config.elite)
+            .map(|(x, f)| ((*x).clone(), Some(*f)))
+            .collect::<Vec<_>>();
+        while next.len() < self.config.population {
+            let a = self.select();
+            let mut child = if self.rng.gen_bool(self.config.crossover_rate) {
+                let b = self.select();
+                let (a, b) = (self.population[a].0.clone(), self.population[b].0.clone());
+                self.crossover(&a, &b)
+            } else {
+                self.population[a].0.clone()
+            };
+            self.mutate(&mut child);
+            next.push((child, None));
+        }
+        self.population = next;
+        self.generation += 1;
+    }
+
+    // run for a number of generations, save a checkpoint after each generation if a path is given
+    pub fn run(&mut self, generations: usize, fitness: &(impl Fn(&AttackerNeural) -> f64 + Sync), checkpoint: Option<&Path>) {
+        for _ in 0..generations {
+            self.step(fitness);
+            if let Some(path) = checkpoint {
+                self.save(path).unwrap();
+            }
+        }
+        self.evaluate(fitness);
+    }
+
+    // tournament selection, returns index into population
+    fn select(&mut self) -> usize {
+        let n = self.population.len();
+        (0..self.config.tournament.max(1))
+            .map(|_| self.rng.gen_range(0..n))
+            .max_by(|&a, &b| {
+                let fa = self.population[a].1.unwrap_or(f64::NEG_INFINITY);
+                let fb = self.population[b].1.unwrap_or(f64::NEG_INFINITY);
+                fa.total_cmp(&fb)
+            })
+            .unwrap()
+    }
+
+    // one-point crossover on each script, uniform word crossover on each projection
+    fn crossover(&mut self, a: &AttackerNeural, b: &AttackerNeural) -> AttackerNeural {
+        let mut child = a.clone();
+        for (c, s) in child.scripts_mut().into_iter().zip(b.scripts()) {
+            let i = self.rng.gen_range(0..=c.len());
+            let j = self.rng.gen_range(0..=s.len());
+            c.truncate(i);
+            c.extend_from_slice(&s[j..]);
+            c.truncate(self.config.max_script_len);
+        }
+        for (c, p) in child.projs_mut().into_iter().zip(b.projs()) {
+            for (x, y) in c.iter_mut().zip(p.iter()) {
+                if self.rng.gen_bool(0.5) { *x = *y; }
+            }
+        }
+        child
+    }
+
+    // point mutation, insertion and deletion on scripts, bit flips on projections
+    fn mutate(&mut self, x: &mut AttackerNeural) {
+        let rate = self.config.mutation_rate;
+        for s in x.scripts_mut() {
+            for w in s.iter_mut() {
+                if self.rng.gen_bool(rate) { *w = self.rng.gen(); }
+            }
+            if self.rng.gen_bool(rate) && s.len() < self.config.max_script_len {
+                let i = self.rng.gen_range(0..=s.len());
+                s.insert(i, self.rng.gen());
+            }
+            if self.rng.gen_bool(rate) && !s.is_empty() {
+                let i = self.rng.gen_range(0..s.len());
+                s.remove(i);
+            }
+        }
+        for p in x.projs_mut() {
+            for w in p.iter_mut() {
+                if self.rng.gen_bool(rate) { *w ^= 1 << self.rng.gen_range(0..32u32); }
+            }
+        }
+    }
+
+    // save generation, population and archive as text
+    // each individual is a line of fitness, then a line of words per script and projection
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "{} {} {}", self.generation, self.population.len(), self.archive.len())?;
+        let population = self.population.iter().map(|(x, f)| (x, f.unwrap_or(f64::NAN)));
+        let archive = self.archive.iter().map(|(x, f)| (x, *f));
+        for (x, f) in population.chain(archive) {
+            writeln!(file, "{f}")?;
+            for s in x.scripts() { writeln!(file, "{}", words(s))?; }
+            for p in x.projs() { writeln!(file, "{}", words(&p[..]))?; }
+        }
+        file.flush()
+    }
+
+    // restore a checkpoint written by save, a resumed run breeds the same generations as an uninterrupted one
+    pub fn load(config: GpConfig, path: &Path) -> std::io::Result<Self> {
+        let bad = |msg: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string());
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let mut lines = file.lines();
+        let mut line = move || lines.next().unwrap_or_else(|| Err(bad("unexpected end of checkpoint")));
+        let header = parse(&line()?).ok_or_else(|| bad("bad header"))?;
+        let [generation, n_population, n_archive] = header[..] else { return Err(bad("bad header")) };
+        let mut individuals = Vec::new();
+        for _ in 0..n_population + n_archive {
+            let f = line()?.trim().parse::<f64>().map_err(|_| bad("bad fitness"))?;
+            let mut x = AttackerNeural::random(&mut StdRng::seed_from_u64(0), 0);
+            for s in x.scripts_mut() {
+                *s = parse(&line()?).ok_or_else(|| bad("bad script"))?;
+            }
+            for p in x.projs_mut() {
+                let w = parse(&line()?).ok_or_else(|| bad("bad projection"))?;
+                if w.len() != p.len() { return Err(bad("bad projection length")) }
+                p.copy_from_slice(&w);
+            }
+            individuals.push((x, f));
+        }
+        let archive = individuals.split_off(n_population as usize);
+        let population = individuals.into_iter()
+            .map(|(x, f)| (x, if f.is_nan() { None } else { Some(f) }))
+            .collect();
+        let rng = breeding_rng(&config, generation as usize);
+        Ok(Self { config, generation: generation as usize, population, archive, rng })
+    }
+}
+
+fn same(a: &AttackerNeural, b: &AttackerNeural) -> bool {
+    a.scripts() == b.scripts() && a.projs() == b.projs()
+}
+
+fn words(s: &[u32]) -> String {
+    s.iter().map(|w| w.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+fn parse(line: &str) -> Option<Vec<u32>> {
+    line.split_whitespace().map(|w| w.parse().ok()).collect()
+}
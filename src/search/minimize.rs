@@ -0,0 +1,119 @@
+use revm::primitives::*;
+use crate::attackers::CallGroup;
+
+// shrink a successful call group while payoff stays at or above threshold
+// payoff plays a call group (e.g. through AttackerFixed in a seeded game), None if the game fails
+// returns the smallest group found and its payoff
+pub fn minimize(group: CallGroup, threshold: f64, payoff: impl Fn(&CallGroup) -> Option<f64>) -> (CallGroup, f64) {
+    let mut best = payoff(&group).expect("call group to minimize must succeed");
+    assert!(best >= threshold, "call group to minimize has payoff {best} below threshold {threshold}");
+    let mut group = group;
+    let mut test = |candidate: &CallGroup| -> bool {
+        match payoff(candidate) {
+            Some(p) if p >= threshold => { best = p; true }
+            _ => false,
+        }
+    };
+    // repeat until no pass makes progress
+    loop {
+        let size = measure(&group);
+        group = remove_calls(group, &mut test);
+        group = shrink_values(group, &mut test);
+        group = shrink_inputs(group, &mut test);
+        if measure(&group) >= size { break }
+    }
+    (group, best)
+}
+
+// number of calls, bits of values, bytes of input
+fn measure(group: &CallGroup) -> (usize, usize, usize) {
+    let calls = group.iter().flatten();
+    (
+        calls.clone().count(),
+        calls.clone().map(|c| c.1.bit_len()).sum(),
+        calls.map(|c| c.2.len() + c.2.iter().filter(|b| **b != 0).count()).sum(),
+    )
+}
+
+// delta debugging over the flattened list of calls
+fn remove_calls(group: CallGroup, test: &mut impl FnMut(&CallGroup) -> bool) -> CallGroup {
+    let mut calls = group.iter().enumerate()
+        .flat_map(|(d, level)| (0..level.len()).map(move |i| (d, i)))
+        .collect::<Vec<_>>();
+    let build = |keep: &[(usize, usize)]| {
+        let mut out = vec![Vec::new(); group.len()];
+        for &(d, i) in keep { out[d].push(group[d][i].clone()); }
+        while out.len() > 1 && out.last().unwrap().is_empty() { out.pop(); }
+        out
+    };
+    let mut n = 2;
+    while calls.len() >= 2 {
+        let chunk = (calls.len() + n - 1) / n;
+        let mut reduced = false;
+        for start in (0..calls.len()).step_by(chunk) {
+            let complement = calls[..start].iter().chain(calls[(start + chunk).min(calls.len())..].iter())
+                .copied().collect::<Vec<_>>();
+            if test(&build(&complement)) {
+                calls = complement;
+                n = (n - 1).max(2);
+                reduced = true;
+                break;
+            }
+        }
+        if !reduced {
+            if n >= calls.len() { break }
+            n = (n * 2).min(calls.len());
+        }
+    }
+    build(&calls)
+}
+
+// try zero, then halve values while the test holds
+fn shrink_values(mut group: CallGroup, test: &mut impl FnMut(&CallGroup) -> bool) -> CallGroup {
+    for d in 0..group.len() {
+        for i in 0..group[d].len() {
+            let value = group[d][i].1;
+            if value == U256::ZERO { continue }
+            let (mut lo, mut hi) = (U256::ZERO, value);
+            // binary search the smallest value that passes, assuming monotonicity
+            while lo < hi {
+                let mid = lo + ((hi - lo) >> 1usize);
+                group[d][i].1 = mid;
+                if test(&group) { hi = mid } else { lo = mid + U256::from(1) }
+            }
+            group[d][i].1 = hi;
+        }
+    }
+    group
+}
+
+// drop trailing bytes, then zero argument words, keeping the selector
+fn shrink_inputs(mut group: CallGroup, test: &mut impl FnMut(&CallGroup) -> bool) -> CallGroup {
+    for d in 0..group.len() {
+        for i in 0..group[d].len() {
+            let input = group[d][i].2.clone();
+            let head = input.len().min(4);
+            // truncate 32 bytes at a time
+            let mut len = input.len();
+            while len >= head + 32 {
+                group[d][i].2 = input.slice(0..len - 32);
+                if !test(&group) { break }
+                len -= 32;
+            }
+            let mut input = input.slice(0..len).to_vec();
+            group[d][i].2 = input.clone().into();
+            for w in (head..len).step_by(32) {
+                let end = (w + 32).min(len);
+                if input[w..end].iter().all(|b| *b == 0) { continue }
+                let word = input[w..end].to_vec();
+                input[w..end].fill(0);
+                group[d][i].2 = input.clone().into();
+                if !test(&group) {
+                    input[w..end].copy_from_slice(&word);
+                    group[d][i].2 = input.clone().into();
+                }
+            }
+        }
+    }
+    group
+}
@@ -0,0 +1,16 @@
+mod gp;
+pub use gp::*;
+mod minimize;
+pub use minimize::*;
+#[cfg(feature = "neural")]
+mod pg;
+#[cfg(feature = "neural")]
+pub use pg::*;
+#[cfg(feature = "neural")]
+mod checkpoint;
+#[cfg(feature = "neural")]
+pub use checkpoint::*;
+#[cfg(feature = "neural")]
+mod pretrain;
+#[cfg(feature = "neural")]
+pub use pretrain::*;
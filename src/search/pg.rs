@@ -0,0 +1,227 @@
+use rayon::prelude::*;
+use tch::{nn, Kind, Tensor};
+use crate::attackers::Episode;
+use crate::neural::{Adam, Model, ValueHead};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Algorithm {
+    Reinforce,
+    // clipped surrogate objective, each batch is used for a number of epochs
+    Ppo { clip: f64, epochs: usize },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Schedule {
+    Constant,
+    // linear from the learning rate to end times the learning rate over all updates
+    Linear { end: f64 },
+    // linear warmup, then cosine decay to zero
+    Cosine { warmup: usize },
+}
+
+#[derive(Clone)]
+pub struct PgConfig {
+    pub algorithm: Algorithm,
+    pub lr: f64,
+    pub schedule: Schedule,
+    // number of updates the schedule spans
+    pub updates: usize,
+    // games per update
+    pub batch: usize,
+    // weight of the entropy bonus
+    pub entropy: f64,
+    // upper limit of the gradient norm
+    pub max_grad_norm: f64,
+    // decay of the moving average payoff used as baseline
+    pub baseline_decay: f64,
+    // sampling temperature of the policy, log probabilities are recomputed with it
+    pub temperature: f64,
+    // weight of the value loss of the critic
+    pub value_coef: f64,
+    // payoffs are divided by this before they are compared with values
+    pub value_scale: f64,
+    pub seed: u64,
+}
+
+impl Default for PgConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: Algorithm::Ppo { clip: 0.2, epochs: 4 }, lr: 3e-4, schedule: Schedule::Constant,
+            updates: 1000, batch: 16, entropy: 0.01, max_grad_norm: 1.0, baseline_decay: 0.9,
+            temperature: 1.0, value_coef: 0.5, value_scale: 1e18, seed: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PgStats {
+    pub update: usize,
+    pub lr: f64,
+    pub games: usize,
+    pub mean_payoff: f64,
+    pub loss: f64,
+    pub entropy: f64,
+    // zero without a critic
+    pub value_loss: f64,
+}
+
+// policy gradient over episodes of token policies, e.g. AttackerTransformer
+// the return of an episode is the payoff of its game, defenders should negate the attacker payoff
+pub struct PolicyGradient {
+    pub config: PgConfig,
+    pub update: usize,
+    // moving average of payoffs, None before the first update
+    pub baseline: Option<f64>,
+    pub opt: Adam,
+    // value head on the policy model, its variables must be in the var store of the optimizer
+    pub critic: Option<ValueHead>,
+}
+
+impl PolicyGradient {
+    pub fn new(vs: &nn::VarStore, config: PgConfig) -> Self {
+        let opt = Adam::new(vs, config.lr);
+        Self { config, update: 0, baseline: None, opt, critic: None }
+    }
+
+    // train a critic along with the policy, for actor-critic advantages
+    pub fn with_critic(mut self, critic: ValueHead) -> Self {
+        self.critic = Some(critic);
+        self
+    }
+
+    pub fn learning_rate(&self) -> f64 {
+        let (lr, t, n) = (self.config.lr, self.update as f64, self.config.updates.max(1) as f64);
+        match self.config.schedule {
+            Schedule::Constant => lr,
+            Schedule::Linear { end } => lr * (1.0 + (end - 1.0) * (t / n).min(1.0)),
+            Schedule::Cosine { warmup } if self.update < warmup => lr * (t + 1.0) / warmup as f64,
+            Schedule::Cosine { warmup } => {
+                let progress = ((t - warmup as f64) / (n - warmup as f64).max(1.0)).min(1.0);
+                lr * 0.5 * (1.0 + (std::f64::consts::PI * progress).cos())
+            }
+        }
+    }
+
+    // log probabilities [n], entropies [n] and values [n] of the choices of an episode under the current model
+    // the value of a choice is that of the prefix before it, there are values only with a critic
+    pub fn evaluate(&self, model: &Model, episode: &Episode) -> (Tensor, Tensor, Option<Tensor>) {
+        let device = model.device();
+        let tokens = Tensor::from_slice(&episode.tokens).reshape([1, episode.tokens.len() as i64]).to_device(device);
+        let hidden = model.hidden(&tokens);
+        let logits = model.logits(&hidden).get(0);
+        let (log_probs, entropies): (Vec<_>, Vec<_>) = episode.choices.iter().map(|choice| {
+            let allowed = Tensor::from_slice(&choice.allowed).to_device(device);
+            let i = choice.allowed.iter().position(|t| *t == choice.token).unwrap() as i64;
+            let lp = (logits.get(choice.position as i64 - 1).index_select(0, &allowed) / self.config.temperature)
+                .log_softmax(-1, Kind::Float);
+            let entropy = -(lp.exp() * &lp).sum(Kind::Float);
+            (lp.get(i), entropy)
+        }).unzip();
+        let values = self.critic.as_ref().map(|critic| {
+            let positions = episode.choices.iter().map(|c| c.position as i64 - 1).collect::<Vec<_>>();
+            critic.forward(&hidden).get(0).index_select(0, &Tensor::from_slice(&positions).to_device(device))
+        });
+        (Tensor::stack(&log_probs, 0), Tensor::stack(&entropies, 0), values)
+    }
+
+    // one update from episodes and their payoffs, episodes without choices are ignored
+    // advantages are over the critic if there is one, else over the moving average payoff
+    pub fn step(&mut self, model: &Model, batch: &[(Episode, f64)]) -> PgStats {
+        let lr = self.learning_rate();
+        self.opt.lr = lr;
+        let mean_payoff = batch.iter().map(|x| x.1).sum::<f64>() / batch.len().max(1) as f64;
+        let baseline = self.baseline.unwrap_or(mean_payoff);
+        let batch = batch.iter().filter(|x| !x.0.choices.is_empty()).collect::<Vec<_>>();
+        let value_scale = self.config.value_scale;
+        // advantage of each choice, scaled to unit deviation within the batch
+        let advantages = batch.iter().map(|(episode, payoff)| match self.critic {
+            Some(_) => {
+                let values = tch::no_grad(|| self.evaluate(model, episode).2.unwrap());
+                Vec::<f64>::try_from(&values.to_kind(Kind::Double)).unwrap().iter().map(|v| payoff / value_scale - v).collect()
+            }
+            None => vec![(payoff - baseline) / value_scale; episode.choices.len()],
+        }).collect::<Vec<Vec<f64>>>();
+        let n = advantages.iter().map(|x| x.len()).sum::<usize>().max(1);
+        let scale = (advantages.iter().flatten().map(|a| a * a).sum::<f64>() / n as f64).sqrt().max(1e-8);
+        let epochs = match self.config.algorithm { Algorithm::Reinforce => 1, Algorithm::Ppo { epochs, .. } => epochs.max(1) };
+        let (mut loss_sum, mut entropy_sum, mut value_sum) = (0.0, 0.0, 0.0);
+        for _ in 0..epochs {
+            if batch.is_empty() { break }
+            let (mut losses, mut entropies, mut value_losses) = (Vec::new(), Vec::new(), Vec::new());
+            for ((episode, payoff), advantage) in batch.iter().zip(&advantages) {
+                let (log_probs, entropy, values) = self.evaluate(model, episode);
+                let device = log_probs.device();
+                let advantage = Tensor::from_slice(&advantage.iter().map(|a| (a / scale) as f32).collect::<Vec<_>>()).to_device(device);
+                let loss = match self.config.algorithm {
+                    Algorithm::Reinforce => -(&log_probs * &advantage).sum(Kind::Float),
+                    Algorithm::Ppo { clip, .. } => {
+                        let old = episode.choices.iter().map(|c| c.log_prob as f32).collect::<Vec<_>>();
+                        let ratio = (&log_probs - Tensor::from_slice(&old).to_device(device)).exp();
+                        let clipped = ratio.clamp(1.0 - clip, 1.0 + clip) * &advantage;
+                        -(ratio * &advantage).minimum(&clipped).sum(Kind::Float)
+                    }
+                };
+                losses.push(loss);
+                entropies.push(entropy.mean(Kind::Float));
+                if let Some(values) = values {
+                    value_losses.push((values - *payoff / value_scale).square().mean(Kind::Float));
+                }
+            }
+            let loss = Tensor::stack(&losses, 0).mean(Kind::Float);
+            let entropy = Tensor::stack(&entropies, 0).mean(Kind::Float);
+            let mut total = &loss - &entropy * self.config.entropy;
+            if !value_losses.is_empty() {
+                let value_loss = Tensor::stack(&value_losses, 0).mean(Kind::Float);
+                total = total + &value_loss * self.config.value_coef;
+                value_sum += value_loss.double_value(&[]);
+            }
+            self.opt.zero_grad();
+            total.backward();
+            self.opt.clip_grad_norm(self.config.max_grad_norm);
+            self.opt.step();
+            loss_sum += loss.double_value(&[]);
+            entropy_sum += entropy.double_value(&[]);
+        }
+        let decay = self.config.baseline_decay;
+        self.baseline = Some(decay * baseline + (1.0 - decay) * mean_payoff);
+        self.update += 1;
+        PgStats {
+            update: self.update, lr, games: batch.len(), mean_payoff,
+            loss: loss_sum / epochs as f64, entropy: entropy_sum / epochs as f64, value_loss: value_sum / epochs as f64,
+        }
+    }
+
+    // play batches of games in parallel and update after each batch
+    // play gets a seed and returns the episode of the policy with the payoff of its game, None if the game fails
+    pub fn train(
+        &mut self,
+        model: &Model,
+        updates: usize,
+        play: impl Fn(u64) -> Option<(Episode, f64)> + Sync,
+    ) -> Vec<PgStats> {
+        (0..updates).map(|_| {
+            let first = self.config.seed.wrapping_add((self.update * self.config.batch) as u64);
+            let batch = (0..self.config.batch as u64).into_par_iter()
+                .filter_map(|i| play(first.wrapping_add(i)))
+                .collect::<Vec<_>>();
+            self.step(model, &batch)
+        }).collect()
+    }
+}
+
+// one regression step of a critic on histories that the policy did not sample, e.g. those of DefenderCritic
+// every position is fitted to the payoff of its game divided by value_scale, returns the mean squared error
+pub fn critic_step(model: &Model, critic: &ValueHead, opt: &mut Adam, batch: &[(Vec<i64>, f64)], value_scale: f64) -> f64 {
+    let block_size = model.config.block_size as usize;
+    let losses = batch.iter().filter(|x| !x.0.is_empty()).map(|(tokens, payoff)| {
+        let tokens = &tokens[tokens.len().saturating_sub(block_size)..];
+        let idx = Tensor::from_slice(tokens).reshape([1, tokens.len() as i64]).to_device(model.device());
+        (critic.forward(&model.hidden(&idx)) - *payoff / value_scale).square().mean(Kind::Float)
+    }).collect::<Vec<_>>();
+    if losses.is_empty() { return 0.0 }
+    let loss = Tensor::stack(&losses, 0).mean(Kind::Float);
+    opt.zero_grad();
+    loss.backward();
+    opt.step();
+    loss.double_value(&[])
+}
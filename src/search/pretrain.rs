@@ -0,0 +1,157 @@
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use rand::prelude::*;
+use rayon::prelude::*;
+use serde_json::{json, Value};
+use tch::{nn, Kind, Tensor};
+use crate::attackers::{CallGroup, Choice, Episode, FuzzCorpus};
+use crate::neural::{Adam, Model};
+
+// exploits found by fuzzing, the plans of a corpus with at least min_payoff
+pub fn corpus_exploits(corpus: &FuzzCorpus, min_payoff: f64) -> Vec<CallGroup> {
+    corpus.entries.iter().filter(|x| x.1 >= min_payoff).map(|x| x.0.clone()).collect()
+}
+
+// play each exploit in parallel, e.g. with AttackerTransformer::imitate, and keep the episodes of those that still pay
+// play returns the episode of the imitating attacker with the payoff of its game, None if the game fails
+pub fn demonstrations(
+    exploits: &[CallGroup],
+    min_payoff: f64,
+    play: impl Fn(&CallGroup) -> Option<(Episode, f64)> + Sync,
+) -> Vec<Episode> {
+    exploits.par_iter()
+        .filter_map(&play)
+        .filter(|(episode, payoff)| *payoff >= min_payoff && !episode.choices.is_empty())
+        .map(|x| x.0)
+        .collect()
+}
+
+// save episodes as json lines, each choice is an example of the next action after the tokens before it
+pub fn save_examples(path: &Path, episodes: &[Episode]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    for episode in episodes {
+        let choices = episode.choices.iter()
+            .map(|c| json!({ "position": c.position, "token": c.token, "allowed": c.allowed }))
+            .collect::<Vec<_>>();
+        writeln!(file, "{}", json!({ "tokens": episode.tokens, "choices": choices }))?;
+    }
+    file.flush()
+}
+
+// load examples written by save_examples, log probabilities are not kept
+pub fn load_examples(path: &Path) -> std::io::Result<Vec<Episode>> {
+    let invalid = |line: usize| Error::new(ErrorKind::InvalidData, format!("bad example line {}", line + 1));
+    let tokens = |x: &Value| x.as_array().and_then(|x| x.iter().map(|t| t.as_i64()).collect::<Option<Vec<_>>>());
+    let mut episodes = Vec::new();
+    for (nr, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        if line.trim().is_empty() { continue }
+        let value = serde_json::from_str::<Value>(line).map_err(|_| invalid(nr))?;
+        let choices = value["choices"].as_array().ok_or_else(|| invalid(nr))?.iter().map(|c| Some(Choice {
+            position: c["position"].as_u64()? as usize,
+            token: c["token"].as_i64()?,
+            log_prob: 0.0,
+            allowed: tokens(&c["allowed"])?,
+        })).collect::<Option<Vec<_>>>().ok_or_else(|| invalid(nr))?;
+        let episode = Episode { tokens: tokens(&value["tokens"]).ok_or_else(|| invalid(nr))?, choices };
+        let bad = |c: &Choice| c.position == 0 || episode.tokens.get(c.position) != Some(&c.token) || !c.allowed.contains(&c.token);
+        if episode.choices.iter().any(bad) {
+            return Err(invalid(nr));
+        }
+        episodes.push(episode);
+    }
+    Ok(episodes)
+}
+
+#[derive(Clone)]
+pub struct BcConfig {
+    pub lr: f64,
+    // episodes per update
+    pub batch: usize,
+    // upper limit of the gradient norm
+    pub max_grad_norm: f64,
+    pub seed: u64,
+}
+
+impl Default for BcConfig {
+    fn default() -> Self {
+        Self { lr: 1e-3, batch: 16, max_grad_norm: 1.0, seed: 0 }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BcStats {
+    pub epoch: usize,
+    // mean negative log likelihood of the demonstrated choices
+    pub loss: f64,
+    // fraction of choices where the demonstrated token is the most likely one
+    pub accuracy: f64,
+}
+
+// behaviour cloning of a token policy on demonstrations, e.g. before PolicyGradient
+// choices are scored among their allowed tokens, as the policy samples them
+pub struct BehaviourCloning {
+    pub config: BcConfig,
+    pub epoch: usize,
+    pub opt: Adam,
+}
+
+impl BehaviourCloning {
+    pub fn new(vs: &nn::VarStore, config: BcConfig) -> Self {
+        let opt = Adam::new(vs, config.lr);
+        Self { config, epoch: 0, opt }
+    }
+
+    // negative log likelihoods [n] of the choices of an episode and how many of them the model ranks first
+    pub fn evaluate(&self, model: &Model, episode: &Episode) -> (Tensor, usize) {
+        let device = model.device();
+        let tokens = Tensor::from_slice(&episode.tokens).reshape([1, episode.tokens.len() as i64]).to_device(device);
+        let logits = model.forward(&tokens).get(0);
+        let mut correct = 0;
+        let nll = episode.choices.iter().map(|choice| {
+            let allowed = Tensor::from_slice(&choice.allowed).to_device(device);
+            let i = choice.allowed.iter().position(|t| *t == choice.token).unwrap() as i64;
+            let lp = logits.get(choice.position as i64 - 1).index_select(0, &allowed).log_softmax(-1, Kind::Float);
+            if lp.argmax(0, false).int64_value(&[]) == i { correct += 1; }
+            -lp.get(i)
+        }).collect::<Vec<_>>();
+        (Tensor::stack(&nll, 0), correct)
+    }
+
+    // one update on a batch of episodes, episodes without choices are ignored
+    pub fn step(&mut self, model: &Model, batch: &[Episode]) -> BcStats {
+        self.update(model, &batch.iter().collect::<Vec<_>>())
+    }
+    fn update(&mut self, model: &Model, batch: &[&Episode]) -> BcStats {
+        let batch = batch.iter().filter(|x| !x.choices.is_empty()).collect::<Vec<_>>();
+        if batch.is_empty() { return BcStats { epoch: self.epoch, loss: 0.0, accuracy: 0.0 } }
+        let (nll, correct): (Vec<_>, Vec<_>) = batch.iter().map(|episode| self.evaluate(model, episode)).unzip();
+        let loss = Tensor::cat(&nll, 0).mean(Kind::Float);
+        self.opt.zero_grad();
+        loss.backward();
+        self.opt.clip_grad_norm(self.config.max_grad_norm);
+        self.opt.step();
+        let n = batch.iter().map(|x| x.choices.len()).sum::<usize>();
+        BcStats { epoch: self.epoch, loss: loss.double_value(&[]), accuracy: correct.iter().sum::<usize>() as f64 / n as f64 }
+    }
+
+    // epochs over the examples in batches, shuffled by the seed and the epoch, returns the stats of each epoch
+    pub fn fit(&mut self, model: &Model, examples: &[Episode], epochs: usize) -> Vec<BcStats> {
+        (0..epochs).map(|_| {
+            let mut order = (0..examples.len()).collect::<Vec<_>>();
+            order.shuffle(&mut StdRng::seed_from_u64(self.config.seed.wrapping_add(self.epoch as u64)));
+            let (mut loss, mut accuracy, mut choices) = (0.0, 0.0, 0);
+            for chunk in order.chunks(self.config.batch.max(1)) {
+                let batch = chunk.iter().map(|i| &examples[*i]).collect::<Vec<_>>();
+                let n = batch.iter().map(|x| x.choices.len()).sum::<usize>();
+                let stats = self.update(model, &batch);
+                loss += stats.loss * n as f64;
+                accuracy += stats.accuracy * n as f64;
+                choices += n;
+            }
+            self.epoch += 1;
+            let n = choices.max(1) as f64;
+            BcStats { epoch: self.epoch, loss: loss / n, accuracy: accuracy / n }
+        }).collect()
+    }
+}
@@ -0,0 +1,44 @@
+use crate::attackers::{apply, assemble, cast_byte, disassemble};
+
+#[test]
+fn test_assembly_roundtrip() {
+    let script = assemble(include_str!("../../test-resources/seeds/echo.asm")).unwrap();
+    assert_eq!(script, vec![0, 0, 18, 13]);
+    assert_eq!(assemble(&disassemble(&script)).unwrap(), script);
+    // opcodes are taken modulo 19
+    assert_eq!(assemble(&disassemble(&[1, 2, 19 + 3])).unwrap(), vec![1, 2, 3]);
+    assert!(assemble("add\ninit 0 0").is_err());
+    assert!(assemble("jump").is_err());
+}
+
+#[test]
+fn test_echo_seed() {
+    let script = assemble(include_str!("../../test-resources/seeds/echo.asm")).unwrap();
+    let mut state = [0u32; 1024];
+    let out = [[0xab, 0xcd].as_slice(), &[0u8; 30]].concat();
+    apply(&mut state, out.into(), &script);
+    assert_eq!(state[0], 0xab000000);
+    assert!(state[1..].iter().all(|x| *x == 0));
+}
+
+#[test]
+fn test_script_vm() {
+    // stores reach every word of the state
+    let mut state = [0u32; 1024];
+    apply(&mut state, Default::default(), &assemble("init 0x5 0x7\nstore").unwrap());
+    assert_eq!(state[5], 7);
+    apply(&mut state, Default::default(), &assemble("init 0x0 0x5\nload\nstore").unwrap());
+    assert_eq!(state[0], 7);
+    // division and remainder by zero, and reading empty bytes, give zero
+    let mut state = [0u32; 1024];
+    apply(&mut state, Default::default(), &assemble("init 0x0 0x9\ndiv\nrem\nswap\nstore").unwrap());
+    assert_eq!(state[0], 0);
+    apply(&mut state, Default::default(), &assemble("init 0x1 0x3\nbyte0\nstore").unwrap());
+    assert_eq!(state[1], 0);
+    // cast_byte packs the selected bits only, and never more than the state holds
+    let (mut a, mut b) = ([0u32; 4], [0u32; 4]);
+    a[0] = 0b1010;
+    b[0] = 0b1110;
+    assert_eq!(cast_byte(1, &a, &b), vec![0b101]);
+    assert_eq!(cast_byte(usize::MAX, &a, &[u32::MAX; 4]).len(), 16);
+}
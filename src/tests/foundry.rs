@@ -0,0 +1,21 @@
+use crate::utils::{Exploit, export_attacker, export_test};
+use revm::primitives::*;
+use super::reentrance_attacker;
+
+#[test]
+fn test_export_foundry() {
+    let (target, account) = (B160::from(1u64), B160::from(2u64));
+    let group = reentrance_attacker(target, account).group().clone();
+    let bin = super::reentrance_bin();
+    let exploit = Exploit {
+        group: &group, contracts: &[target], abis: &[super::reentrance_abi()],
+        init_codes: &[bin], attacker: account,
+    };
+    let attacker = export_attacker(&exploit);
+    // calls are labelled and addresses are replaced by the deployed ones
+    assert!(attacker.contains(&format!("// donate({:?})", ethers::types::Address::from(account))));
+    assert!(attacker.contains("// withdraw(10000)"));
+    assert!(attacker.contains("targets[0].call{value: 10000}"));
+    assert!(attacker.contains("bytes32(uint256(uint160(address(this))))"));
+    assert!(export_test(&exploit).contains("new Attacker(targets)"));
+}
@@ -0,0 +1,85 @@
+use crate::attackers::{AttackerFuzz, FuzzConfig, FuzzCorpus, fuzz_campaign};
+use crate::{defenders, environment};
+use revm::primitives::*;
+
+#[test]
+fn test_fuzz_plan() {
+    let abi = super::reentrance_abi();
+    let selectors = abi.functions().map(|f| f.short_signature().to_vec()).collect::<Vec<_>>();
+    let contracts = [(B160::from(1u64), Bytes::default())];
+    let plan = AttackerFuzz::new(vec![abi.clone()], FuzzConfig::default(), 7).plan(&contracts);
+    // same seed, same plan
+    assert!(plan == AttackerFuzz::new(vec![abi], FuzzConfig::default(), 7).plan(&contracts));
+    assert!(!plan[0].is_empty());
+    for (_, _, input) in plan.iter().flatten() {
+        assert!(input.is_empty() || selectors.contains(&input[..4].to_vec()));
+    }
+}
+
+#[test]
+fn test_fuzz_without_contracts() {
+    use crate::environment::interfaces::Attacker;
+    let mut fuzz = AttackerFuzz::new(Vec::new(), FuzzConfig::default(), 7);
+    assert!(fuzz.plan(&[]).is_empty());
+    let (_, mut state) = fuzz.init(&[]);
+    assert!(fuzz.make_mal_call(&mut state).is_none());
+}
+
+#[test]
+fn test_fuzz_campaign() {
+    let abi = super::reentrance_abi();
+    let bin = super::reentrance_bin();
+    // games share a seed, so that every game sees the same addresses
+    let setup = || {
+        let mut env = environment::Environment::with_seed(10, 0);
+        env.load_contracts(vec![bin.clone()]);
+        let attacker = env.create_attacker_account();
+        (env, attacker)
+    };
+    let (env, attacker) = setup();
+    let contracts = env.get_contracts().to_vec();
+    let make = |seed| AttackerFuzz::new(vec![abi.clone()], FuzzConfig::default(), seed).with_addresses(vec![attacker]);
+    let mut corpus = FuzzCorpus::default();
+    fuzz_campaign(make, &contracts, 0..16, &mut corpus, |fuzz| {
+        let (mut env, _) = setup();
+        env.load_attacker(fuzz);
+        env.load_defender(defenders::DefenderPermissive);
+        env.payoff()
+    });
+    assert!(!corpus.entries.is_empty());
+}
+
+#[test]
+fn test_coverage_fuzzer() {
+    use crate::attackers::CoverageFuzzer;
+    let abi = super::reentrance_abi();
+    let bin = super::reentrance_bin();
+    let setup = || {
+        let mut env = environment::Environment::with_seed(10, 1);
+        env.load_contracts(vec![bin.clone()]);
+        let attacker = env.create_attacker_account();
+        (env, attacker)
+    };
+    let (env, attacker) = setup();
+    let seed = AttackerFuzz::new(vec![abi], FuzzConfig::default(), 0).plan(env.get_contracts());
+    // an empty corpus takes any seed, even one that was never played
+    let mut corpus = FuzzCorpus::default();
+    assert!(corpus.consider(seed, f64::NEG_INFINITY));
+    assert_eq!(corpus.entries.len(), 1);
+    let mut fuzzer = CoverageFuzzer::new(corpus, vec![attacker], 0);
+    fuzzer.run(32, |fixed, sink| {
+        let (mut env, _) = setup();
+        env.track_coverage(sink);
+        env.load_attacker(fixed);
+        env.load_defender(defenders::DefenderPermissive);
+        env.payoff()
+    }, None);
+    assert!(!fuzzer.coverage.is_empty());
+    assert!(fuzzer.corpus.entries.len() > 1);
+    // the corpus survives a round trip through disk
+    let path = std::env::temp_dir().join(format!("eth-game-corpus-{}.txt", std::process::id()));
+    fuzzer.corpus.save(&path).unwrap();
+    let loaded = FuzzCorpus::load(&path).unwrap();
+    assert_eq!(loaded.entries.len(), fuzzer.corpus.entries.len());
+    assert!(loaded.entries.iter().zip(fuzzer.corpus.entries.iter()).all(|(a, b)| a.0 == b.0));
+}
@@ -0,0 +1,276 @@
+use crate::attackers::AttackerFixed;
+use crate::environment::{Environment, GameReport, interfaces::Defender};
+use crate::defenders;
+use ethers::abi::Token;
+use revm::primitives::*;
+
+// a reentrant attacker and an attacker that only donates against Reentrance, with a user that donates and withdraws
+fn play_with(defender: impl Defender) -> GameReport {
+    let abi = super::reentrance_abi();
+    let bin = super::reentrance_bin();
+    let donate = |to: B160| -> Bytes {
+        abi.function("donate").unwrap().encode_input(&[Token::Address(to.into())]).unwrap().into()
+    };
+    let withdraw: Bytes = abi.function("withdraw").unwrap().encode_input(&[Token::Uint(100.into())]).unwrap().into();
+    let mut env = Environment::with_seed(64, 3);
+    env.load_contracts(vec![bin]);
+    let target = env.get_contracts()[0].0;
+    let first = env.create_attacker_account();
+    let second = env.create_attacker_account();
+    env.load_attacker(super::reentrance_attacker(target, first));
+    env.load_attacker(AttackerFixed::new(vec![vec![(target, U256::from(1), donate(second))]]));
+    let user = env.create_user_account();
+    env.load_user(vec![(target, U256::from(100), donate(user)), (target, U256::ZERO, withdraw)]);
+    env.set_rounds(4, 1);
+    env.load_defender(defender);
+    env.play().unwrap()
+}
+
+#[test]
+fn test_multiple_agents() {
+    let report = play_with(defenders::DefenderPermissive);
+    assert_eq!(report.balances.len(), 2);
+    assert_eq!(report.benign_calls, 2);
+    assert_eq!(report.false_positive_rate(), 0.0);
+    // denying every call stops the attackers, and every benign user with them
+    let report = play_with(defenders::DefenderDenial);
+    assert!(report.payoff() <= 0.0);
+    assert_eq!(report.false_positive_rate(), 1.0);
+}
+
+#[test]
+fn test_attacker_code() {
+    use crate::environment::AttackerCode;
+    let bin = super::reentrance_bin();
+    let play = |code: Option<AttackerCode>| {
+        let mut env = Environment::with_seed(10, 42);
+        env.load_contracts(vec![bin.clone()]);
+        let target = env.get_contracts()[0].0;
+        let account = match &code {
+            Some(code) => env.create_attacker_account_with(code),
+            None => env.create_attacker_account(),
+        };
+        let group = super::reentrance_attacker(target, account).group().clone();
+        env.load_attacker(AttackerFixed::new(group));
+        env.load_defender(defenders::DefenderPermissive);
+        env.payoff()
+    };
+    // stop behaves like the default attacker account
+    assert_eq!(play(None), play(Some(AttackerCode::Stop)));
+    // an attacker that rejects transfers gets nothing out of withdraw
+    assert!(play(Some(AttackerCode::Revert)) < play(Some(AttackerCode::Stop)));
+}
+
+#[test]
+fn test_attacker_eoa() {
+    let bin = super::reentrance_bin();
+    let play = |eoa: bool| {
+        let mut env = Environment::with_seed(10, 42);
+        env.load_contracts(vec![bin.clone()]);
+        let target = env.get_contracts()[0].0;
+        let account = if eoa { env.create_attacker_eoa() } else { env.create_attacker_account() };
+        let group = super::reentrance_attacker(target, account).group().clone();
+        env.load_attacker(AttackerFixed::new(group));
+        env.load_defender(defenders::DefenderPermissive);
+        env.payoff().unwrap()
+    };
+    // reentrancy needs an attacker contract, a plain account only gets its donation back
+    assert_eq!(play(true), 0.0);
+    assert!(play(false) > 0.0);
+}
+
+#[test]
+fn test_mixed_attackers() {
+    let bin = super::reentrance_bin();
+    // each attacker keeps its own kind, whichever account is created last
+    let play = |eoa_first: bool| {
+        let mut env = Environment::with_seed(64, 42);
+        env.load_contracts(vec![bin.clone()]);
+        let target = env.get_contracts()[0].0;
+        let (eoa, contract) = if eoa_first {
+            let eoa = env.create_attacker_eoa();
+            (eoa, env.create_attacker_account())
+        } else {
+            let contract = env.create_attacker_account();
+            (env.create_attacker_eoa(), contract)
+        };
+        let accounts = if eoa_first { [eoa, contract] } else { [contract, eoa] };
+        for account in accounts {
+            env.load_attacker(AttackerFixed::new(super::reentrance_attacker(target, account).group().clone()));
+        }
+        env.load_defender(defenders::DefenderPermissive);
+        let report = env.play().unwrap();
+        let payoffs = report.balances.iter().map(|(x, y)| crate::utils::payoff(*x, *y)).collect::<Vec<_>>();
+        if eoa_first { (payoffs[0], payoffs[1]) } else { (payoffs[1], payoffs[0]) }
+    };
+    for eoa_first in [true, false] {
+        let (eoa, contract) = play(eoa_first);
+        assert_eq!(eoa, 0.0);
+        assert!(contract > 0.0);
+    }
+}
+
+#[test]
+fn test_benign_workload() {
+    use crate::defenders::{BenignWorkload, false_positive_rate};
+    let abi = super::reentrance_abi();
+    let bin = super::reentrance_bin();
+    let (target, user) = (B160::from(1u64), B160::from(2u64));
+    let script = BenignWorkload::new(vec![abi.clone()], 0).script(user, &[target], 16);
+    assert_eq!(script.len(), 16);
+    // only payable calls carry value
+    let donate = abi.function("donate").unwrap().short_signature();
+    assert!(script.iter().all(|(_, value, input)| *value == U256::ZERO || input[..4] == donate));
+    let codes = [bin];
+    assert_eq!(false_positive_rate(&codes, &[abi.clone()], defenders::DefenderPermissive, 4, 8, 0), 0.0);
+    assert_eq!(false_positive_rate(&codes, &[abi], defenders::DefenderDenial, 4, 8, 0), 1.0);
+}
+
+// rejects the call that starts its move, so every game it plays fails
+struct Failing;
+
+impl crate::environment::interfaces::Attacker for Failing {
+    type State = ();
+    fn init(&mut self, _contracts: &[(B160, Bytes)]) -> (bool, Self::State) { (true, ()) }
+    fn make_mal_call(&self, _state: &mut Self::State) -> Option<(B160, U256, Bytes)> { None }
+    fn take_return(&self, _state: &mut Self::State, _ret: revm::interpreter::InstructionResult, _gas: revm::interpreter::Gas, _out: Bytes) {}
+    fn check(&self, _state: &mut Self::State) -> bool { false }
+}
+
+#[test]
+fn test_tournament() {
+    use crate::environment::interfaces::{BoxAttacker, BoxDefender};
+    use crate::game::Tournament;
+    let matrix = Tournament::new(vec![super::reentrance_target()], 10)
+        .attacker("reentrance", |s| BoxAttacker::new(super::reentrance_attacker(s.targets[0].0, s.attacker)))
+        .attacker("idle", |_| BoxAttacker::new(AttackerFixed::new(Vec::new())))
+        .attacker("failing", |_| BoxAttacker::new(Failing))
+        .defender("permissive", |_| BoxDefender::new(defenders::DefenderPermissive))
+        .defender("denial", |_| BoxDefender::new(defenders::DefenderDenial))
+        .repeats(2)
+        .run();
+    assert_eq!(matrix.samples[0][0][0].len(), 2);
+    assert!(matrix.mean(0, 0) > 0.0);
+    assert!(matrix.mean(0, 1) <= 0.0);
+    assert_eq!(matrix.means()[1], vec![0.0, 0.0]);
+    // failed games are counted apart from the payoffs
+    assert_eq!(matrix.failures(0, 0), 0);
+    assert_eq!(matrix.failures(2, 0), 2);
+    assert!(matrix.samples[2][0][0].is_empty());
+    assert_eq!(matrix.to_csv().lines().count(), 1 + 3 * 2 * 2);
+    assert!(matrix.to_json().starts_with('{'));
+}
+
+#[test]
+fn test_nash() {
+    use crate::game::{exploitability, fictitious_play, regret_matching, solve_lp};
+    // rock paper scissors
+    let rps = vec![vec![0.0, -1.0, 1.0], vec![1.0, 0.0, -1.0], vec![-1.0, 1.0, 0.0]];
+    let eq = solve_lp(&rps);
+    assert!(eq.value.abs() < 1e-9);
+    assert!(eq.attacker.iter().chain(eq.defender.iter()).all(|x| (x - 1.0 / 3.0).abs() < 1e-9));
+    // the second attacker is dominated, the defender mixes 1/4 and 3/4
+    let game = vec![vec![3.0, -1.0], vec![-2.0, -2.0], vec![-3.0, 1.0]];
+    let eq = solve_lp(&game);
+    assert!((eq.value - 0.0).abs() < 1e-9);
+    assert!(eq.attacker[1].abs() < 1e-9);
+    assert!((eq.defender[0] - 0.25).abs() < 1e-9);
+    assert!(exploitability(&game, &eq) < 1e-9);
+    for eq in [fictitious_play(&game, 20000), regret_matching(&game, 20000)] {
+        assert!(eq.value.abs() < 0.05);
+        assert!(exploitability(&game, &eq) < 0.05);
+    }
+}
+
+#[test]
+fn test_psro() {
+    use crate::game::{Psro, PsroConfig, Tournament};
+    use crate::search::GpConfig;
+    let dir = std::env::temp_dir().join(format!("eth-game-psro-{}", std::process::id()));
+    let config = PsroConfig {
+        generations: 1, gp: GpConfig { population: 4, ..GpConfig::default() }, candidates: 4,
+        users: 2, calls: 4, checkpoint: Some(dir.clone()), ..PsroConfig::default()
+    };
+    let mut psro = Psro::new(Tournament::new(vec![super::reentrance_target()], 10), config);
+    let eq = psro.run(2);
+    assert_eq!(psro.matrix.len(), psro.attackers.len());
+    assert!(psro.matrix.iter().all(|row| row.len() == psro.defenders.len()));
+    assert!((eq.attacker.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    assert!((eq.defender.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    // the oracle payoff is a meta-game payoff, even if every game of the best attacker fails
+    let (_, payoff) = psro.attacker_oracle(&eq.defender);
+    assert!(payoff.is_finite());
+    assert!(dir.join("meta").exists());
+    assert!(dir.join("attacker0.corpus").exists() && dir.join("defender0.guard").exists());
+}
+
+#[test]
+fn test_guard() {
+    use crate::defenders::DefenderGuard;
+    // one level of calls into the target is enough for donate and withdraw, but not for reentrancy
+    let guard = DefenderGuard { max_depth: 1, ..DefenderGuard::permissive() };
+    assert!(super::seeded_game(super::reentrance_attacker, guard.clone()).unwrap() <= 0.0);
+    assert!(super::seeded_game(super::reentrance_attacker, DefenderGuard::permissive()).unwrap() > 0.0);
+    let path = std::env::temp_dir().join(format!("eth-game-guard-{}", std::process::id()));
+    let guard = DefenderGuard { blocked: vec![[1, 2, 3, 4]], ..guard };
+    guard.save(&path).unwrap();
+    assert_eq!(DefenderGuard::load(&path).unwrap(), guard);
+}
+
+// remembers the depth of every call into a target that was not reverted, and rejects calls deeper than max_depth
+struct Trail {
+    max_depth: usize,
+    trails: std::sync::Arc<std::sync::Mutex<Vec<Vec<usize>>>>,
+}
+
+impl Defender for Trail {
+    type State = (usize, Vec<usize>);
+    fn init(&mut self, _contracts: &[(B160, Bytes)]) -> Self::State {
+        (0, Vec::new())
+    }
+    fn check(&self, state: &Self::State, _inputs: &revm::interpreter::CallInputs) -> (Self::State, bool) {
+        let depth = state.0 + 1;
+        let trail = state.1.iter().copied().chain([depth]).collect();
+        ((depth, trail), depth <= self.max_depth)
+    }
+    fn call_end(&self, caller: &mut Self::State, state: Self::State, ok: bool) {
+        if ok { caller.1 = state.1; }
+    }
+    fn finish(&mut self, state: Self::State) {
+        self.trails.lock().unwrap().push(state.1);
+    }
+}
+
+#[test]
+fn test_defender_history() {
+    let trail = |max_depth| {
+        let trails = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        super::seeded_game(super::reentrance_attacker, Trail { max_depth, trails: trails.clone() }).unwrap();
+        let trails = trails.lock().unwrap().clone();
+        assert_eq!(trails.len(), 1);
+        trails[0].clone()
+    };
+    // reentrant calls finish inside the calls that made them
+    let open = trail(usize::MAX);
+    assert!(open.contains(&1) && open.contains(&2));
+    // rejected calls are reverted and leave no trace
+    let guarded = trail(1);
+    assert!(!guarded.is_empty() && guarded.iter().all(|x| *x == 1));
+}
+
+#[test]
+fn test_stackelberg() {
+    use crate::defenders::DefenderGuard;
+    use crate::game::{AttackerSearch, Stackelberg, Tournament};
+    let arena = Tournament::new(vec![super::reentrance_target()], 10);
+    let game = Stackelberg::new(arena, AttackerSearch::Fuzz { seeds: 8 }).false_positive_cost(1e18, 2, 4);
+    let deny = DefenderGuard { max_value: U256::ZERO, max_depth: 0, ..DefenderGuard::permissive() };
+    let guards = vec![DefenderGuard::permissive(), DefenderGuard { max_depth: 1, ..DefenderGuard::permissive() }, deny];
+    let (best, commitments) = game.optimise(guards);
+    assert_eq!(commitments.len(), 3);
+    // the idle attacker bounds every best response from below
+    assert!(commitments.iter().all(|c| c.payoff >= 0.0));
+    assert!(commitments.iter().all(|c| game.loss(&commitments[best]) <= game.loss(c)));
+    // rejecting every call rejects every benign call
+    assert_eq!(commitments[2].false_positive_rate, 1.0);
+}
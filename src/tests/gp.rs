@@ -0,0 +1,51 @@
+use crate::search::{Gp, GpConfig};
+
+#[test]
+fn test_gp_archive_and_checkpoint() {
+    // toy fitness: number of set bits in the value projection
+    let fitness = |x: &crate::attackers::AttackerNeural| {
+        x.projs()[1].iter().map(|w| w.count_ones()).sum::<u32>() as f64
+    };
+    let mut gp = Gp::new(GpConfig { population: 16, ..Default::default() });
+    gp.run(1, &fitness, None);
+    let first = gp.best().unwrap().1;
+    gp.run(4, &fitness, None);
+    assert!(gp.best().unwrap().1 >= first);
+    let path = std::env::temp_dir().join(format!("eth-game-gp-checkpoint-{}.txt", std::process::id()));
+    gp.save(&path).unwrap();
+    let loaded = Gp::load(GpConfig { population: 16, ..Default::default() }, &path).unwrap();
+    assert_eq!(loaded.generation, gp.generation);
+    assert_eq!(loaded.best().unwrap().1, gp.best().unwrap().1);
+}
+
+#[test]
+fn test_gp_resume() {
+    let fitness = |x: &crate::attackers::AttackerNeural| {
+        x.projs()[1].iter().map(|w| w.count_ones()).sum::<u32>() as f64
+    };
+    let config = GpConfig { population: 8, ..Default::default() };
+    let mut uninterrupted = Gp::new(config.clone());
+    uninterrupted.run(3, &fitness, None);
+    let path = std::env::temp_dir().join(format!("eth-game-gp-resume-{}.txt", std::process::id()));
+    Gp::new(config.clone()).run(1, &fitness, Some(&path));
+    let mut resumed = Gp::load(config, &path).unwrap();
+    resumed.run(2, &fitness, None);
+    let scripts = |gp: &Gp| gp.population.iter().map(|x| x.0.scripts().map(|s| s.clone())).collect::<Vec<_>>();
+    assert_eq!(scripts(&resumed), scripts(&uninterrupted));
+    let archive = |gp: &Gp| gp.archive.iter().map(|x| x.1).collect::<Vec<_>>();
+    assert_eq!(archive(&resumed), archive(&uninterrupted));
+}
+
+#[test]
+fn test_gp_game_fitness() {
+    use crate::defenders::DefenderPermissive;
+    use crate::search::game_fitness;
+    let bin = super::reentrance_bin();
+    let fitness = game_fitness(vec![bin], 10, 42, || DefenderPermissive);
+    let mut gp = Gp::new(GpConfig { population: 4, ..Default::default() });
+    gp.run(1, &fitness, None);
+    assert!(gp.population.iter().all(|x| x.1.is_some()));
+    // seeded games make fitness reproducible
+    let x = &gp.population[0];
+    assert_eq!(fitness(&x.0), x.1.unwrap());
+}
@@ -0,0 +1,28 @@
+use crate::attackers::AttackerFixed;
+use crate::defenders;
+use crate::search::minimize;
+use revm::primitives::*;
+use super::{reentrance_attacker, seeded_game};
+
+#[test]
+fn test_minimize_reentrance() {
+    // the seeded game gives the same addresses as in every other game
+    let mut group = None;
+    seeded_game(|target, account| {
+        let attacker = reentrance_attacker(target, account);
+        group = Some(attacker.group().clone());
+        attacker
+    }, defenders::DefenderPermissive);
+    let mut group = group.unwrap();
+    // add noise: a plain transfer that gains nothing
+    let target = group[0][0].0;
+    group[0].insert(0, (target, U256::from(12345), Bytes::from(vec![0u8; 68])));
+    let play = |group: &Vec<Vec<(B160, U256, Bytes)>>| {
+        seeded_game(|_, _| AttackerFixed::new(group.clone()), defenders::DefenderPermissive)
+    };
+    let (small, payoff) = minimize(group.clone(), 1.0, play);
+    assert!(payoff >= 1.0);
+    let count = |g: &Vec<Vec<(B160, U256, Bytes)>>| g.iter().flatten().count();
+    assert!(count(&small) < count(&group));
+    assert_eq!(play(&small), Some(payoff));
+}
@@ -1,8 +1,33 @@
-use crate::environment::{self, interfaces::Defender};
+mod assembly;
+mod gp;
+mod fuzz;
+mod replay;
+mod minimize;
+mod foundry;
+mod game;
+#[cfg(feature = "neural")]
+mod neural;
+
+use crate::environment::{self, interfaces::{Attacker, Defender}};
 use crate::{attackers, defenders};
 use ethers::abi::Token;
 
-fn test_environment_with<const TRACE: bool>(defender: impl Defender) {
+// bytecode of the Reentrance contract
+fn reentrance_bin() -> revm::primitives::Bytes {
+    hex::decode(include_str!("../../test-resources/Reentrance.bin")).unwrap().into()
+}
+
+fn reentrance_abi() -> ethers::abi::Abi {
+    crate::utils::load_abi("test-resources/Reentrance.abi")
+}
+
+// Reentrance as a tournament target
+fn reentrance_target() -> crate::game::Target {
+    crate::game::Target { name: "Reentrance".to_string(), init_code: reentrance_bin(), abi: reentrance_abi() }
+}
+
+// donate to the attacker account, then withdraw reentrantly
+fn reentrance_attacker(target: revm::primitives::B160, attacker_account: revm::primitives::B160) -> attackers::AttackerFixed {
     use ethers::abi::parse_abi;
     use revm::primitives::*;
     let abi = parse_abi(&[
@@ -10,21 +35,37 @@ fn test_environment_with<const TRACE: bool>(defender: impl Defender) {
         "function balanceOf(address _who) public view returns (uint balance)",
         "function withdraw(uint _amount) public",
     ]).unwrap();
-    let bin = hex::decode(include_str!("../../test-resources/Reentrance.bin")).unwrap().into();
-    let mut env = environment::Environment::<_, _, TRACE>::new(10);
-    env.load_contracts(vec![bin]);
-    let target = env.get_contracts()[0].0;
-    let attacker_account = env.create_attacker_account();
-    let x = env.attacker_balance();
     let donate : Bytes = abi.function("donate").unwrap().encode_input(&[Token::Address(attacker_account.into())]).unwrap().into();
     let withdraw: Bytes = abi.function("withdraw").unwrap().encode_input(&[Token::Uint(U256::from(10000).into())]).unwrap().into();
-    let attacker = attackers::AttackerFixed::new(vec![
+    attackers::AttackerFixed::new(vec![
         vec![(target, U256::from(0), withdraw.clone()), (target, U256::from(10000), donate)],
         vec![(target, U256::from(0), withdraw.clone())],
         vec![(target, U256::from(0), withdraw.clone())],
         vec![(target, U256::from(0), withdraw.clone())],
         vec![(target, U256::from(0), withdraw.clone())],
-    ]);
+    ])
+}
+
+// a seeded game against Reentrance, so that addresses are the same in every game
+fn seeded_game<A: Attacker, D: Defender>(make: impl FnOnce(revm::primitives::B160, revm::primitives::B160) -> A, defender: D) -> Option<f64> {
+    let bin = reentrance_bin();
+    let mut env = environment::Environment::<A, D>::with_seed(10, 42);
+    env.load_contracts(vec![bin]);
+    let target = env.get_contracts()[0].0;
+    let attacker_account = env.create_attacker_account();
+    env.load_attacker(make(target, attacker_account));
+    env.load_defender(defender);
+    env.payoff()
+}
+
+fn test_environment_with<const TRACE: bool>(defender: impl Defender) {
+    let bin = reentrance_bin();
+    let mut env = environment::Environment::<_, _, TRACE>::new(10);
+    env.load_contracts(vec![bin]);
+    let target = env.get_contracts()[0].0;
+    let attacker_account = env.create_attacker_account();
+    let x = env.attacker_balances()[0];
+    let attacker = reentrance_attacker(target, attacker_account);
     env.load_attacker(attacker);
     env.load_defender(defender);
     let y = env.compute();
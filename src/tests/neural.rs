@@ -0,0 +1,308 @@
+use std::sync::Arc;
+use crate::attackers::{AttackerTransformer, CallGroup, Episode, TransformerConfig};
+use crate::defenders::DefenderPermissive;
+use crate::neural::{device, parse_device, var_store, Config, Model};
+use tch::{Device, Kind, Tensor};
+
+fn small() -> Config {
+    Config { vocab_size: 32, n_layer: 2, n_head: 2, n_embd: 16, block_size: 64 }
+}
+
+// a seeded game of a transformer attacker against a permissive defender, with the episode of the attacker
+// with a teacher the attacker makes the calls of the group instead of sampling them
+fn play_transformer(model: &Arc<Model>, seed: u64, teacher: Option<&CallGroup>) -> Option<(Episode, f64)> {
+    let mut handle = None;
+    let payoff = super::seeded_game(|_, account| {
+        let config = TransformerConfig { seed, code_len: 64, ..Default::default() };
+        let (attacker, episode) = AttackerTransformer::new(model.clone(), vec![super::reentrance_abi()], account, config);
+        handle = Some(episode);
+        match teacher {
+            Some(group) => attacker.imitate(group.clone()),
+            None => attacker,
+        }
+    }, DefenderPermissive)?;
+    let episode = handle.unwrap().lock().unwrap().clone();
+    Some((episode, payoff))
+}
+
+#[test]
+fn test_model_forward() {
+    let vs = var_store();
+    let model = Model::new(vs.root(), small());
+    let idx = Tensor::randint(32, [3, 10], (Kind::Int64, device()));
+    assert_eq!(model.forward(&idx).size(), vec![3, 10, 32]);
+    // causal, changing a later token does not change earlier logits
+    let mut other = idx.copy();
+    let _ = other.narrow(1, 9, 1).fill_(0);
+    let (a, b) = (model.forward(&idx).narrow(1, 0, 9), model.forward(&other).narrow(1, 0, 9));
+    assert!(a.allclose(&b, 1e-5, 1e-5, false));
+}
+
+#[test]
+fn test_bytecode_tokenizer() {
+    use crate::neural::*;
+    let tokenizer = BytecodeTokenizer::default();
+    // PUSH1 0x80 PUSH1 0x40 MSTORE
+    assert_eq!(tokenizer.tokenize(&[0x60, 0x80, 0x60, 0x40, 0x52]),
+        vec![BOS, OPCODE + 0x60, IMMEDIATE + 0x80, OPCODE + 0x60, IMMEDIATE + 0x40, OPCODE + 0x52, EOS]);
+    // DUP1 PUSH4 selector EQ JUMPDEST
+    let code = [0x80, 0x63, 0x2e, 0x1a, 0x7d, 0x4d, 0x14, 0x5b];
+    assert_eq!(tokenizer.tokenize(&code), vec![
+        BOS, OPCODE + 0x80, SELECTOR, OPCODE + 0x63,
+        IMMEDIATE + 0x2e, IMMEDIATE + 0x1a, IMMEDIATE + 0x7d, IMMEDIATE + 0x4d,
+        OPCODE + 0x14, JUMPDEST, EOS,
+    ]);
+    assert_eq!(selectors(&code), vec![[0x2e, 0x1a, 0x7d, 0x4d]]);
+    // truncated code and sequences keep their end marker
+    let short = BytecodeTokenizer { max_len: 4, max_immediate: 1 };
+    assert_eq!(short.tokenize(&[0x61, 0xff, 0xff, 0x00, 0x00]), vec![BOS, OPCODE + 0x61, IMMEDIATE + 0xff, EOS]);
+    assert_eq!(tokenizer.tokenize(&[0x62, 0x01]), vec![BOS, OPCODE + 0x62, IMMEDIATE + 0x01, EOS]);
+    // BOS and EOS are kept however small the limit
+    for max_len in [0, 1, 2] {
+        assert_eq!(BytecodeTokenizer { max_len, max_immediate: 1 }.tokenize(&code), vec![BOS, EOS]);
+    }
+    let batch = tokenizer.batch(&[&code, &[0x00]], device());
+    assert_eq!(batch.size(), vec![2, 11]);
+    let vs = var_store();
+    let embedding = BytecodeEmbedding::new(vs.root(), 16);
+    assert_eq!(embedding.forward(&batch).size(), vec![2, 11, 16]);
+}
+
+#[test]
+fn test_trace_encoder() {
+    use crate::neural::*;
+    use revm::interpreter::InstructionResult;
+    use revm::primitives::*;
+    let (target, attacker) = (B160::from(1u64), B160::from(2u64));
+    // a dispatcher comparing with withdraw(uint256)
+    let code: Bytes = vec![0x80, 0x63, 0x2e, 0x1a, 0x7d, 0x4d, 0x14].into();
+    let encoder = TraceEncoder::new(&[(target, code)], attacker);
+    assert_eq!(encoder.selectors, vec![[0x2e, 0x1a, 0x7d, 0x4d]]);
+    let mut input = vec![0x2e, 0x1a, 0x7d, 0x4d];
+    input.extend_from_slice(&U256::from(10000).to_be_bytes::<32>());
+    input.extend_from_slice(&[0u8; 32]);
+    input.extend_from_slice(&[[0u8; 12].as_slice(), attacker.as_bytes()].concat());
+    let call = (target, U256::from(1) << 70usize, Bytes::from(input));
+    let tokens = encoder.encode_event(&TraceEvent::Call { address: call.0, value: call.1, input: call.2.clone() });
+    assert_eq!(tokens[..4], [CALL, TARGET, VALUE + 71, SELECTOR_INDEX]);
+    assert!(tokens.iter().all(|t| (0..VOCAB).contains(t)));
+    assert_eq!(encoder.decode_call(&tokens), Some(call.clone()));
+    // unknown selectors and addresses are spelled out
+    let other = (B160::from(3u64), U256::ZERO, Bytes::from(vec![1, 2, 3, 4]));
+    let tokens = encoder.encode_event(&TraceEvent::Call { address: other.0, value: other.1, input: other.2.clone() });
+    assert_eq!(encoder.decode_call(&tokens), Some(other));
+    let history = encoder.encode(&[
+        TraceEvent::Call { address: call.0, value: call.1, input: call.2 },
+        TraceEvent::Return { status: InstructionResult::Revert, out: Bytes::default() },
+    ]);
+    assert_eq!(history[0], BOS);
+    assert_eq!(history[history.len() - 2..], [RETURN, STATUS_REVERT]);
+    assert_eq!(encoder.tensor(&history, device()).size(), vec![1, history.len() as i64]);
+}
+
+#[test]
+fn test_attacker_transformer() {
+    use crate::neural::VOCAB;
+    let vs = var_store();
+    let model = Arc::new(Model::new(vs.root(), Config { vocab_size: VOCAB, block_size: 512, ..small() }));
+    let (episode, _) = play_transformer(&model, 1, None).unwrap();
+    assert!(!episode.choices.is_empty());
+    for choice in episode.choices.iter() {
+        assert_eq!(episode.tokens[choice.position], choice.token);
+        assert!(choice.allowed.contains(&choice.token) && choice.log_prob <= 0.0);
+    }
+    assert!(episode.tokens.len() <= 512);
+    // sampling is reproducible from the seed
+    assert_eq!(play_transformer(&model, 1, None).unwrap().0.tokens, episode.tokens);
+}
+
+#[test]
+fn test_policy_gradient() {
+    use crate::neural::VOCAB;
+    use crate::search::{Algorithm, PgConfig, PolicyGradient, Schedule};
+    let vs = var_store();
+    let model = Arc::new(Model::new(vs.root(), Config { vocab_size: VOCAB, block_size: 512, ..small() }));
+    let play = |seed| play_transformer(&model, seed, None);
+    for algorithm in [Algorithm::Reinforce, Algorithm::Ppo { clip: 0.2, epochs: 2 }] {
+        let before = vs.trainable_variables().iter().map(|x| x.copy()).collect::<Vec<_>>();
+        let config = PgConfig { algorithm, batch: 2, updates: 2, schedule: Schedule::Linear { end: 0.5 }, ..Default::default() };
+        let mut trainer = PolicyGradient::new(&vs, config);
+        let stats = trainer.train(&model, 2, &play);
+        assert_eq!(stats.len(), 2);
+        assert!(stats.iter().all(|s| s.loss.is_finite() && s.entropy >= 0.0));
+        assert!(stats[1].lr < stats[0].lr);
+        let changed = vs.trainable_variables().iter().zip(&before).any(|(a, b)| !a.allclose(b, 0.0, 0.0, false));
+        assert!(changed);
+    }
+}
+
+#[test]
+fn test_checkpoints() {
+    use crate::search::{Checkpoints, PgConfig, PolicyGradient, Schedule};
+    let dir = std::env::temp_dir().join(format!("eth-game-checkpoints-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let checkpoints = Checkpoints::open(&dir).unwrap();
+    assert_eq!(checkpoints.latest().unwrap(), None);
+    let vs = var_store();
+    let model = Model::new(vs.root(), small());
+    let config = PgConfig { schedule: Schedule::Cosine { warmup: 2 }, seed: 7, ..Default::default() };
+    let mut trainer = PolicyGradient::new(&vs, config);
+    // one step on a made up episode
+    let idx = Tensor::randint(32, [1, 8], (Kind::Int64, device()));
+    let loss = model.forward(&idx).mean(Kind::Float);
+    trainer.opt.zero_grad();
+    loss.backward();
+    trainer.opt.step();
+    trainer.update = 3;
+    trainer.baseline = Some(1.5);
+    let first = checkpoints.save(&vs, &model.config, &trainer, "first", None).unwrap();
+    let second = checkpoints.save(&vs, &model.config, &trainer, "second", Some(first)).unwrap();
+    assert_eq!(checkpoints.latest().unwrap().map(|x| (x.id, x.parent)), Some((second, Some(first))));
+    // resume into a fresh model built from the stored configs
+    let (resumed_vs, resumed_model, resumed) = checkpoints.resume(second, Model::new).unwrap();
+    assert_eq!(resumed_model.config.n_embd, 16);
+    assert_eq!((resumed.update, resumed.baseline, resumed.opt.step, resumed.config.seed), (3, Some(1.5), 1, 7));
+    assert_eq!(resumed.learning_rate(), trainer.learning_rate());
+    let (a, b) = (vs.variables(), resumed_vs.variables());
+    assert!(a.iter().all(|(name, x)| x.allclose(&b[name], 0.0, 0.0, false)));
+}
+
+#[test]
+fn test_checkpoint_without_critic_config() {
+    use crate::search::{Checkpoints, PgConfig, PolicyGradient};
+    let dir = std::env::temp_dir().join(format!("eth-game-old-checkpoints-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let checkpoints = Checkpoints::open(&dir).unwrap();
+    let vs = var_store();
+    let model = Model::new(vs.root(), small());
+    let config = PgConfig { value_coef: 2.0, value_scale: 1.0, seed: 3, ..Default::default() };
+    let id = checkpoints.save(&vs, &model.config, &PolicyGradient::new(&vs, config), "old", None).unwrap();
+    // a version 1 state as written before the critic settings
+    let path = checkpoints.path(id).join("state.json");
+    let mut state = serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(state["version"].as_u64(), Some(1));
+    let trainer = state["trainer"].as_object_mut().unwrap();
+    trainer.remove("value_coef");
+    trainer.remove("value_scale");
+    std::fs::write(&path, state.to_string()).unwrap();
+    let (_, trainer) = checkpoints.config(id).unwrap();
+    let default = PgConfig::default();
+    assert_eq!((trainer.value_coef, trainer.value_scale, trainer.seed), (default.value_coef, default.value_scale, 3));
+    let (_, _, resumed) = checkpoints.resume(id, Model::new).unwrap();
+    assert_eq!(resumed.config.value_coef, default.value_coef);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_parse_device() {
+    assert_eq!(parse_device("cpu"), Some(Device::Cpu));
+    assert_eq!(parse_device(" CUDA "), Some(Device::Cuda(0)));
+    assert_eq!(parse_device("cuda:1"), Some(Device::Cuda(1)));
+    assert_eq!(parse_device("mps"), Some(Device::Mps));
+    assert_eq!(parse_device("cuda:x"), None);
+    assert_eq!(parse_device("tpu"), None);
+}
+
+#[test]
+fn test_kv_cache() {
+    use crate::neural::ModelCache;
+    let vs = var_store();
+    let model = Model::new(vs.root(), small());
+    let idx = Tensor::randint(32, [2, 13], (Kind::Int64, device()));
+    let full = model.forward(&idx.narrow(1, 0, 12));
+    // a prefix of 5 tokens, then one token at a time
+    let mut cache = ModelCache::default();
+    let mut steps = vec![model.forward_cached(&idx.narrow(1, 0, 5), &mut cache)];
+    for t in 5..12 {
+        steps.push(model.forward_cached(&idx.narrow(1, t, 1), &mut cache));
+    }
+    assert_eq!(cache.len(), 12);
+    assert!(Tensor::cat(&steps, 1).allclose(&full, 1e-4, 1e-5, false));
+    // reordering the batch of a cache reorders the next step, as if the flipped sequences were run in full
+    let mut cache = cache.index_select(&Tensor::from_slice(&[1i64, 0]).to_device(device()));
+    let flipped = idx.flip([0]);
+    let next = model.forward_cached(&flipped.narrow(1, 12, 1), &mut cache);
+    assert!(next.allclose(&model.forward(&flipped).narrow(1, 12, 1), 1e-4, 1e-5, false));
+}
+
+#[test]
+fn test_critics() {
+    use crate::defenders::DefenderCritic;
+    use crate::neural::{Adam, ValueHead, VOCAB};
+    use crate::search::{critic_step, Algorithm, PgConfig, PolicyGradient};
+    let vs = var_store();
+    let model = Arc::new(Model::new(vs.root() / "model", Config { vocab_size: VOCAB, block_size: 512, ..small() }));
+    // actor-critic on the attacker
+    let play = |seed| play_transformer(&model, seed, None);
+    let critic = ValueHead::new(vs.root() / "critic", 16);
+    let config = PgConfig { algorithm: Algorithm::Ppo { clip: 0.2, epochs: 2 }, batch: 2, ..Default::default() };
+    let mut trainer = PolicyGradient::new(&vs, config).with_critic(critic);
+    let stats = trainer.train(&model, 1, &play);
+    assert!(stats[0].value_loss > 0.0 && stats[0].value_loss.is_finite());
+    // the danger score of a defender critic gates calls into the target
+    let defender_vs = var_store();
+    let critic = Arc::new(ValueHead::new(defender_vs.root() / "critic", 16));
+    let (open, histories) = DefenderCritic::new(model.clone(), critic.clone(), f64::INFINITY);
+    assert!(super::seeded_game(super::reentrance_attacker, open).unwrap() > 0.0);
+    let seen = histories.lock().unwrap().clone();
+    assert_eq!(seen.len(), 1);
+    let seen = seen[0].clone();
+    assert!(seen.contains(&crate::neural::CHECK));
+    // rejected calls are left out of the history
+    let (closed, histories) = DefenderCritic::new(model.clone(), critic.clone(), f64::NEG_INFINITY);
+    assert!(super::seeded_game(super::reentrance_attacker, closed).unwrap() <= 0.0);
+    let rejected = histories.lock().unwrap().clone();
+    assert_eq!(rejected.len(), 1);
+    assert!(!rejected[0].contains(&crate::neural::CHECK));
+    assert!(seen.starts_with(&rejected[0]));
+    // fitting the critic to a payoff lowers its error
+    let mut opt = Adam::new(&defender_vs, 1e-2);
+    let batch = vec![(seen, 2e18)];
+    let first = critic_step(&model, &critic, &mut opt, &batch, 1e18);
+    let last = (0..20).map(|_| critic_step(&model, &critic, &mut opt, &batch, 1e18)).last().unwrap();
+    assert!(last < first);
+}
+
+#[test]
+fn test_behaviour_cloning() {
+    use crate::attackers::AttackerFixed;
+    use crate::neural::{CALL, VOCAB};
+    use crate::search::{demonstrations, load_examples, save_examples, BcConfig, BehaviourCloning};
+    let vs = var_store();
+    let model = Arc::new(Model::new(vs.root(), Config { vocab_size: VOCAB, block_size: 512, ..small() }));
+    // addresses are the same in every seeded game
+    let mut addresses = None;
+    super::seeded_game(|target, account| {
+        addresses = Some((target, account));
+        AttackerFixed::new(Vec::new())
+    }, DefenderPermissive);
+    let (target, account) = addresses.unwrap();
+    let exploit = super::reentrance_attacker(target, account).group().clone();
+    let play = |group: &CallGroup| play_transformer(&model, 0, Some(group));
+    // the imitating attacker still exploits the target and records the calls as choices
+    let examples = demonstrations(&[exploit.clone(), Vec::new()], f64::MIN_POSITIVE, play);
+    assert_eq!(examples.len(), 1);
+    let episode = &examples[0];
+    assert!(episode.tokens.iter().filter(|t| **t == CALL).count() >= 2);
+    for choice in episode.choices.iter() {
+        assert_eq!(episode.tokens[choice.position], choice.token);
+        assert!(choice.allowed.contains(&choice.token) && choice.log_prob == 0.0);
+    }
+    let path = std::env::temp_dir().join(format!("eth-game-examples-{}.jsonl", std::process::id()));
+    save_examples(&path, &examples).unwrap();
+    let loaded = load_examples(&path).unwrap();
+    assert_eq!(loaded[0].tokens, episode.tokens);
+    assert_eq!(loaded[0].choices.len(), episode.choices.len());
+    // a choice of a token that was not allowed is refused with its line
+    std::fs::write(&path, "\n{\"tokens\": [1, 2], \"choices\": [{\"position\": 1, \"token\": 2, \"allowed\": [3]}]}\n").unwrap();
+    let error = load_examples(&path).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains("line 2"));
+    std::fs::remove_file(&path).unwrap();
+    // cloning the demonstration makes it more likely
+    let mut trainer = BehaviourCloning::new(&vs, BcConfig { lr: 1e-2, ..Default::default() });
+    let stats = trainer.fit(&model, &loaded, 20);
+    assert_eq!(stats.len(), 20);
+    assert!(stats[19].loss < stats[0].loss);
+    assert!(stats[19].accuracy >= stats[0].accuracy);
+}
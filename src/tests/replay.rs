@@ -0,0 +1,28 @@
+use crate::attackers::{AttackerRecord, AttackerReplay, Recording};
+use crate::defenders;
+use super::seeded_game as game;
+
+#[test]
+fn test_record_and_replay() {
+    let mut recording = None;
+    let recorded = game(|target, account| {
+        let (attacker, handle) = AttackerRecord::new(super::reentrance_attacker(target, account));
+        recording = Some(handle);
+        attacker
+    }, defenders::DefenderPermissive);
+    let recording = recording.unwrap().lock().unwrap().clone();
+    assert!(!recording.events.is_empty());
+    // the recording survives a round trip through disk
+    let path = std::env::temp_dir().join(format!("eth-game-recording-{}.txt", std::process::id()));
+    recording.save(&path).unwrap();
+    assert_eq!(Recording::load(&path).unwrap(), recording);
+    // replay against the same defender does not diverge
+    let (replay, divergences) = AttackerReplay::new(recording.clone());
+    let replayed = game(|_, _| replay, defenders::DefenderPermissive);
+    assert_eq!(recorded, replayed);
+    assert!(divergences.lock().unwrap().is_empty());
+    // replay against a defender that denies every call diverges
+    let (replay, divergences) = AttackerReplay::new(recording);
+    game(|_, _| replay, defenders::DefenderDenial);
+    assert!(!divergences.lock().unwrap().is_empty());
+}
@@ -0,0 +1,161 @@
+use std::fmt::Write;
+use ethers::abi::{Abi, Token};
+use revm::primitives::{B160, Bytes};
+use crate::attackers::CallGroup;
+
+// an exploit found in a game, to be exported as solidity
+pub struct Exploit<'a> {
+    // calls at each reentrancy depth, in the format of AttackerFixed
+    pub group: &'a CallGroup,
+    // target addresses in the game and their abis (may be fewer than targets)
+    pub contracts: &'a [B160],
+    pub abis: &'a [Abi],
+    // init code of each target, used to deploy targets in the test
+    pub init_codes: &'a [Bytes],
+    // attacker account in the game, its occurrences in calldata are replaced by the exported attacker
+    pub attacker: B160,
+}
+
+// write src/Attacker.sol and test/Exploit.t.sol into a foundry project directory
+pub fn export_foundry(exploit: &Exploit, dir: &std::path::Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir.join("src"))?;
+    std::fs::create_dir_all(dir.join("test"))?;
+    std::fs::write(dir.join("src/Attacker.sol"), export_attacker(exploit))?;
+    std::fs::write(dir.join("test/Exploit.t.sol"), export_test(exploit))
+}
+
+// an attacker contract that makes the calls of the exploit at each reentrancy depth
+// like AttackerFixed, every entry at depth d continues with the calls left at depth d
+pub fn export_attacker(exploit: &Exploit) -> String {
+    let group = exploit.group;
+    let mut s = String::new();
+    writeln!(s, "// SPDX-License-Identifier: UNLICENSED").unwrap();
+    writeln!(s, "pragma solidity ^0.8.0;\n").unwrap();
+    writeln!(s, "contract Attacker {{").unwrap();
+    writeln!(s, "    address[] public targets;").unwrap();
+    writeln!(s, "    uint256 private depth;").unwrap();
+    writeln!(s, "    uint256[{}] private next;\n", group.len().max(1)).unwrap();
+    writeln!(s, "    event CallResult(uint256 depth, uint256 index, bool ok);\n").unwrap();
+    writeln!(s, "    constructor(address[] memory _targets) payable {{ targets = _targets; }}\n").unwrap();
+    writeln!(s, "    function attack() external payable {{ _enter(); }}").unwrap();
+    writeln!(s, "    receive() external payable {{ _enter(); }}").unwrap();
+    writeln!(s, "    fallback() external payable {{ _enter(); }}\n").unwrap();
+    writeln!(s, "    function _enter() internal {{").unwrap();
+    writeln!(s, "        uint256 d = depth;").unwrap();
+    writeln!(s, "        depth = d + 1;").unwrap();
+    writeln!(s, "        while (d < {} && next[d] < _count(d)) {{", group.len()).unwrap();
+    writeln!(s, "            uint256 i = next[d]++;").unwrap();
+    writeln!(s, "            _call(d, i);").unwrap();
+    writeln!(s, "        }}").unwrap();
+    writeln!(s, "        depth = d;").unwrap();
+    writeln!(s, "    }}\n").unwrap();
+    writeln!(s, "    function _count(uint256 d) internal pure returns (uint256) {{").unwrap();
+    for (d, calls) in group.iter().enumerate() {
+        writeln!(s, "        if (d == {d}) return {};", calls.len()).unwrap();
+    }
+    writeln!(s, "        return 0;").unwrap();
+    writeln!(s, "    }}\n").unwrap();
+    writeln!(s, "    function _call(uint256 d, uint256 i) internal {{").unwrap();
+    for (d, calls) in group.iter().enumerate() {
+        // calls are popped from the back
+        for (i, (address, value, input)) in calls.iter().rev().enumerate() {
+            if let Some(label) = label(exploit.abis, input) {
+                writeln!(s, "        // {label}").unwrap();
+            }
+            writeln!(s, "        if (d == {d} && i == {i}) {{").unwrap();
+            writeln!(s, "            (bool ok, ) = {}.call{{value: {value}}}({});", address_expr(exploit, *address), input_expr(exploit, input)).unwrap();
+            writeln!(s, "            emit CallResult(d, i, ok);").unwrap();
+            writeln!(s, "            return;").unwrap();
+            writeln!(s, "        }}").unwrap();
+        }
+    }
+    writeln!(s, "    }}").unwrap();
+    writeln!(s, "}}").unwrap();
+    s
+}
+
+// a foundry test that deploys and funds targets the way Environment does, then runs the attacker
+pub fn export_test(exploit: &Exploit) -> String {
+    let n = exploit.init_codes.len();
+    let mut s = String::new();
+    writeln!(s, "// SPDX-License-Identifier: UNLICENSED").unwrap();
+    writeln!(s, "pragma solidity ^0.8.0;\n").unwrap();
+    writeln!(s, "import \"forge-std/Test.sol\";").unwrap();
+    writeln!(s, "import \"../src/Attacker.sol\";\n").unwrap();
+    writeln!(s, "contract ExploitTest is Test {{").unwrap();
+    writeln!(s, "    function _deploy(bytes memory code) internal returns (address target) {{").unwrap();
+    writeln!(s, "        assembly {{ target := create(0, add(code, 0x20), mload(code)) }}").unwrap();
+    writeln!(s, "        require(target != address(0), \"deploy failed\");").unwrap();
+    writeln!(s, "        (bool ok, ) = target.call{{value: uint256(type(uint64).max) / {}}}(\"\");", n.max(1)).unwrap();
+    writeln!(s, "        require(ok, \"funding failed\");").unwrap();
+    writeln!(s, "    }}\n").unwrap();
+    writeln!(s, "    function test_exploit() public {{").unwrap();
+    writeln!(s, "        vm.deal(address(this), type(uint128).max);").unwrap();
+    writeln!(s, "        address[] memory targets = new address[]({n});").unwrap();
+    for (i, code) in exploit.init_codes.iter().enumerate() {
+        writeln!(s, "        targets[{i}] = _deploy(hex\"{}\");", hex::encode(code)).unwrap();
+    }
+    writeln!(s, "        Attacker attacker = new Attacker(targets);").unwrap();
+    writeln!(s, "        vm.deal(address(attacker), type(uint64).max);").unwrap();
+    writeln!(s, "        uint256 before = address(attacker).balance;").unwrap();
+    writeln!(s, "        attacker.attack();").unwrap();
+    writeln!(s, "        emit log_named_uint(\"attacker balance before\", before);").unwrap();
+    writeln!(s, "        emit log_named_uint(\"attacker balance after\", address(attacker).balance);").unwrap();
+    writeln!(s, "        assertGt(address(attacker).balance, before);").unwrap();
+    writeln!(s, "    }}").unwrap();
+    writeln!(s, "}}").unwrap();
+    s
+}
+
+// decoded call, e.g. `withdraw(10000)`, if some abi knows the selector
+fn label(abis: &[Abi], input: &Bytes) -> Option<String> {
+    if input.len() < 4 { return None }
+    let function = abis.iter().flat_map(|abi| abi.functions()).find(|f| f.short_signature() == input[..4])?;
+    let tokens = function.decode_input(&input[4..]).ok()?;
+    // Token displays integers and addresses in hex without a prefix
+    let args = tokens.iter().map(|t| match t {
+        Token::Address(x) => format!("{x:?}"),
+        Token::Uint(x) => x.to_string(),
+        Token::Int(x) => ethers::types::I256::from_raw(*x).to_string(),
+        t => t.to_string(),
+    }).collect::<Vec<_>>().join(", ");
+    Some(format!("{}({args})", function.name))
+}
+
+// targets are referred to by index, other addresses are written out
+fn address_expr(exploit: &Exploit, address: B160) -> String {
+    if address == exploit.attacker {
+        return "address(this)".to_string();
+    }
+    match exploit.contracts.iter().position(|x| *x == address) {
+        Some(i) => format!("targets[{i}]"),
+        None => format!("address(uint160(0x{}))", hex::encode(address)),
+    }
+}
+
+// calldata with argument words holding the attacker or a target replaced by their exported addresses
+fn input_expr(exploit: &Exploit, input: &Bytes) -> String {
+    let head = input.len().min(4);
+    let mut parts = vec![hex::encode(&input[..head])];
+    let mut literal = true;
+    for word in input[head..].chunks(32) {
+        let address = (word.len() == 32 && word[..12].iter().all(|b| *b == 0))
+            .then(|| B160::from_slice(&word[12..]))
+            .filter(|a| *a == exploit.attacker || exploit.contracts.contains(a));
+        match address {
+            Some(address) => {
+                parts.push(format!("bytes32(uint256(uint160({})))", address_expr(exploit, address)));
+                literal = false;
+            }
+            None => parts.push(hex::encode(word)),
+        }
+    }
+    if literal {
+        return format!("hex\"{}\"", hex::encode(input));
+    }
+    let parts = parts.into_iter()
+        .map(|p| if p.starts_with("bytes32") { p } else { format!("hex\"{p}\"") })
+        .collect::<Vec<_>>();
+    format!("abi.encodePacked({})", parts.join(", "))
+}
+
@@ -1,5 +1,7 @@
+mod foundry;
+pub use foundry::*;
 use ethers::abi::Abi;
-use revm::primitives::Bytes;
+use revm::primitives::{Bytes, U256};
 use std::process::Command;
 
 pub fn compile_solidity(solc: &str, source: &str) -> (Bytes, Abi) {
@@ -24,4 +26,18 @@ pub fn compile_solidity(solc: &str, source: &str) -> (Bytes, Abi) {
         .spawn().unwrap()
         .wait().unwrap();
     (bin.into(), abi)
-}
\ No newline at end of file
+}
+
+// load an abi json file, e.g. test-resources/Reentrance.abi
+pub fn load_abi(path: &str) -> Abi {
+    Abi::load(std::fs::OpenOptions::new().read(true).open(path).unwrap()).unwrap()
+}
+// convert an unsigned integer to floating point
+pub fn to_f64(x: U256) -> f64 {
+    x.as_limbs().iter().rev().fold(0.0, |acc, &limb| acc * 2f64.powi(64) + limb as f64)
+}
+
+// signed balance change from x to y
+pub fn payoff(x: U256, y: U256) -> f64 {
+    if y >= x { to_f64(y - x) } else { -to_f64(x - y) }
+}
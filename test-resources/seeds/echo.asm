@@ -0,0 +1,4 @@
+; copy the first byte of a call return into the top byte of state[0]
+init 0x0 0x0   ; x = 0 is the index, y = 0 the byte offset
+byte3          ; y = out[0] << 24
+store          ; state[x] = y